@@ -1,209 +1,204 @@
 #![windows_subsystem = "windows"]
 
-use windows::core::*;
+mod policy;
+mod registry;
+mod supervisor;
+mod trace;
 
-use windows::Win32::Foundation::*;
-use windows::Win32::Graphics::{
-    Dwm::{DwmSetWindowAttribute, DWMWA_CAPTION_COLOR},
-    Gdi::*,
-};
-use windows::Win32::System::LibraryLoader::{GetModuleFileNameW, GetModuleHandleW};
-use windows::Win32::System::Threading::CreateProcessW;
-use windows::Win32::UI::WindowsAndMessaging::*;
+#[cfg(unix)]
+mod terminal;
+#[cfg(windows)]
+mod window;
+
+use policy::Policy;
 
 fn main() {
-    let args: Vec<_> = std::env::args().skip(1).collect();
+    let mut args = std::env::args().skip(1).peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("trace") => {
+            args.next();
+            console();
+            trace::command(args);
+            return;
+        }
+        Some("heads") => {
+            console();
+            for entry in registry::live() {
+                println!(
+                    "{}\t{}\t{}\t{:?}",
+                    entry.pid, entry.generation, entry.parent, entry.text
+                );
+            }
+            return;
+        }
+        Some("supervise") => {
+            args.next();
+            let (head, policy, text) = parse(args);
+            supervisor::run(head, policy, text);
+            return;
+        }
+        Some("ctl") => {
+            args.next();
+            console();
+            supervisor::command(args);
+            return;
+        }
+        _ => {}
+    }
 
-    let text = args.join(" ");
-    MainWindow::new(Some(text));
-}
+    let (head, policy, text) = parse(args);
+
+    // Better to refuse now than to have the population die out at the first
+    // close.
+    #[cfg(unix)]
+    if !head.headless && terminal::display().is_none() {
+        eprintln!(
+            "every head needs a terminal of its own: run inside tmux, with a terminal \
+             emulator on a display, or with --headless"
+        );
+        std::process::exit(1);
+    }
+
+    if !registry::register(&head, &policy, &text) {
+        return;
+    }
+
+    // Heads started by hand add another root to the trace rather than wiping
+    // out sessions that are still running; `trace --clear` starts afresh.
+    trace::log_spawn(head.parent, head.generation);
+    supervisor::register(&head);
+
+    #[cfg(windows)]
+    window::MainWindow::new(head, policy, Some(text));
 
-#[derive(Default)]
-struct MainWindow {
-    window: HWND,
-    edit_field: HWND,
+    #[cfg(unix)]
+    terminal::run(head, policy, text);
+
+    registry::unregister();
+    trace::log_exit(std::process::id());
 }
 
-impl MainWindow {
-    fn new(text: Option<String>) -> Self {
-        let mut main_window = Self::default();
-
-        unsafe {
-            let class_name = w!("Hasher Window Class").into();
-
-            let hinstance = GetModuleHandleW(None).expect("Can't get module handle");
-
-            assert!(!hinstance.is_invalid());
-
-            assert_ne!(
-                RegisterClassW(&WNDCLASSW {
-                    lpfnWndProc: Some(Self::wndproc),
-                    hInstance: hinstance,
-                    lpszClassName: class_name,
-                    ..Default::default()
-                }),
-                0
-            );
-
-            main_window.window = CreateWindowExW(
-                WS_EX_ACCEPTFILES | WS_EX_DLGMODALFRAME,
-                class_name,
-                w!("Gorynych"),
-                WS_OVERLAPPEDWINDOW,
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
-                350,
-                240,
-                None,
-                None,
-                hinstance,
-                Some(&mut main_window as *mut _ as _),
-            );
-
-            debug_assert!(main_window.window.0 != 0);
-
-            let monofont = CreateFontW(
-                24,
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-                DEFAULT_CHARSET.0,
-                OUT_DEFAULT_PRECIS,
-                CLIP_DEFAULT_PRECIS,
-                CLEARTYPE_QUALITY,
-                FF_DONTCARE,
-                w!("Cascadia Code"),
-            );
-
-            main_window.edit_field = CreateWindowExW(
-                WINDOW_EX_STYLE::default(),
-                w!("Edit"),
-                w!(""),
-                WS_VISIBLE | WS_CHILD | WS_VSCROLL | WINDOW_STYLE((ES_MULTILINE) as _),
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
-                main_window.window,
-                None,
-                hinstance,
-                None,
-            );
-
-            if let Some(text) = text {
-                SetWindowTextW(main_window.edit_field, &HSTRING::from(text));
-            }
+// A windows subsystem program starts without a console, and whatever the
+// commands print would go nowhere. Borrow the one of the shell they were run
+// from.
+#[cfg(windows)]
+fn console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
 
-            SendMessageW(
-                main_window.edit_field,
-                WM_SETFONT,
-                WPARAM(monofont.0 as _),
-                LPARAM(1),
-            );
-
-            _ = DwmSetWindowAttribute(
-                main_window.window,
-                DWMWA_CAPTION_COLOR,
-                &[
-                    0xEE, // Red
-                    0xEE, // Green
-                    0xEE, // Blue
-                    0,    // Zero
-                ],
-            );
-
-            ShowWindow(main_window.window, SW_SHOW);
-
-            let mut message = MSG::default();
-
-            while GetMessageW(&mut message, None, 0, 0).into() {
-                TranslateMessage(&message);
-                DispatchMessageW(&message);
-            }
-        }
-        main_window
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
     }
+}
 
-    extern "system" fn wndproc(
-        window: HWND,
-        message: u32,
-        wparam: WPARAM,
-        lparam: LPARAM,
-    ) -> LRESULT {
-        unsafe {
-            if message == WM_NCCREATE {
-                let cs = lparam.0 as *const CREATESTRUCTA;
-                let this = (*cs).lpCreateParams as *mut Self;
-                (*this).window = window;
-
-                SetWindowLongPtrW(window, GWLP_USERDATA, this as _);
-            } else {
-                let this = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Self;
-
-                if !this.is_null() {
-                    return (*this).message_handler(message, wparam, lparam);
-                }
-            }
+#[cfg(unix)]
+fn console() {}
+
+const HEAD_FLAGS: [&str; 5] = ["generation", "parent", "position", "supervisor", "config"];
+
+fn parse(mut args: impl Iterator<Item = String>) -> (Head, Policy, String) {
+    let mut head = Head::default();
+    let mut policy = Policy::default();
+    let mut words = vec![];
 
-            DefWindowProcW(window, message, wparam, lparam)
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            words.extend(args.by_ref());
+            break;
+        }
+
+        if arg == "--headless" {
+            head.headless = true;
+            continue;
         }
-    }
 
-    fn message_handler(&self, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-        unsafe {
-            match message {
-                WM_GETMINMAXINFO => {
-                    let min_size = &mut (*(lparam.0 as *mut MINMAXINFO)).ptMinTrackSize;
-                    min_size.x = 200;
-                    min_size.y = 120;
-                }
-                WM_SIZE => {
-                    let width = (lparam.0 as u16) as i32;
-                    let height = (lparam.0 >> 16) as i32;
-
-                    MoveWindow(self.edit_field, 0, 0, width, height, true);
-                    InvalidateRect(self.edit_field, None, None);
-                }
-                WM_DESTROY => {
-                    for _ in 0..3 {
-                        self.clone();
-                    }
-
-                    PostQuitMessage(0)
-                }
-                _ => return DefWindowProcW(self.window, message, wparam, lparam),
-            };
-            LRESULT(0)
+        // Anything that isn't one of our flags is text, even if it looks
+        // like one.
+        let key = match arg.strip_prefix("--") {
+            Some(key) if HEAD_FLAGS.contains(&key) || Policy::KEYS.contains(&key) => key,
+            _ => {
+                words.push(arg);
+                continue;
+            }
+        };
+
+        let value = args
+            .next()
+            .unwrap_or_else(|| panic!("--{} needs a value", key));
+
+        match key {
+            "generation" => head.generation = value.parse().expect("--generation needs a number"),
+            "parent" => head.parent = value.parse().expect("--parent needs a pid"),
+            "position" => {
+                let (x, y) = value.split_once(',').expect("--position needs x,y");
+                head.position = Some((
+                    x.parse().expect("bad x position"),
+                    y.parse().expect("bad y position"),
+                ));
+            }
+            "supervisor" => {
+                head.supervisor = Some(value.parse().expect("--supervisor needs a pid"))
+            }
+            "config" => policy.load(&value),
+            _ => {
+                policy.set(key, &value);
+            }
         }
     }
 
-    unsafe fn clone(&self) {
-        let mut app_name: Vec<u16> = vec![0; MAX_PATH as _];
-        GetModuleFileNameW(None, &mut app_name);
+    (head, policy, words.join(" "))
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct Head {
+    pub generation: u32,
+    pub parent: u32,
+    pub position: Option<(i32, i32)>,
+    pub headless: bool,
+    // The pid of the supervisor, which names its control socket.
+    pub supervisor: Option<u32>,
+}
+
+impl Head {
+    // Flags that turn a new process into the successor of the head `pid`. The
+    // text has to be appended after them, following `--`.
+    pub fn child_args(
+        &self,
+        pid: u32,
+        policy: &Policy,
+        position: Option<(i32, i32)>,
+    ) -> Vec<String> {
+        let mut args = vec![
+            "--generation".to_string(),
+            (self.generation + 1).to_string(),
+            "--parent".to_string(),
+            pid.to_string(),
+        ];
+
+        if let Some((x, y)) = position {
+            args.push("--position".to_string());
+            args.push(format!("{},{}", x, y));
+        }
 
-        let mut buff = vec![0; GetWindowTextLengthW(self.edit_field) as usize + 1];
+        if self.headless {
+            args.push("--headless".to_string());
+        }
 
-        GetWindowTextW(self.edit_field, &mut buff);
+        if let Some(supervisor) = self.supervisor {
+            args.push("--supervisor".to_string());
+            args.push(supervisor.to_string());
+        }
 
-        let mut command = Vec::from(w!("app ").as_wide());
+        args.extend(policy.to_args());
+        args
+    }
 
-        command.append(&mut buff);
+    // How many successors to spawn when this head goes away.
+    pub fn successors(&self, policy: &Policy, abnormal: bool) -> u32 {
+        if !policy.should_respawn(abnormal) {
+            return 0;
+        }
 
-        CreateProcessW(
-            PCWSTR::from_raw(app_name.as_mut_ptr()),
-            PWSTR::from_raw(command.as_mut_ptr()),
-            None,
-            None,
-            false,
-            Default::default(),
-            Default::default(),
-            PCWSTR::from_raw(0 as _),
-            &Default::default() as _,
-            &mut Default::default() as _,
-        );
+        policy.heads.min(registry::room(policy))
     }
 }
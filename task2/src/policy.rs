@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RespawnOn {
+    Close,
+    // Only what a head notices itself: a hangup or an interrupt in a
+    // terminal, a window destroyed without WM_CLOSE or a session ending on
+    // Windows. A head that crashes or is killed outright (SIGKILL,
+    // `taskkill /F`) never gets to respawn; only a supervisor catches those,
+    // by the exit code.
+    Abnormal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Placement {
+    Same,
+    Cascade,
+}
+
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub heads: u32,
+    pub delay: Duration,
+    pub backoff: f64,
+    pub max_delay: Duration,
+    pub respawn_on: RespawnOn,
+    pub placement: Placement,
+    pub max_heads: u32,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            heads: 3,
+            delay: Duration::ZERO,
+            backoff: 1.0,
+            max_delay: Duration::from_secs(60),
+            respawn_on: RespawnOn::Close,
+            placement: Placement::Same,
+            max_heads: 0,
+        }
+    }
+}
+
+const CASCADE_STEP: i32 = 30;
+
+impl Policy {
+    pub const KEYS: [&'static str; 7] = [
+        "heads",
+        "delay",
+        "backoff",
+        "max-delay",
+        "respawn-on",
+        "placement",
+        "max-heads",
+    ];
+
+    // Returns false when `key` is not a policy setting, so callers can handle
+    // their own flags in the same loop.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "heads" => self.heads = value.parse().expect("heads must be a number"),
+            "delay" => {
+                self.delay = Duration::from_millis(value.parse().expect("delay must be in ms"))
+            }
+            "backoff" => {
+                self.backoff = value.parse().expect("backoff must be a number");
+                assert!(
+                    self.backoff.is_finite() && self.backoff >= 1.0,
+                    "backoff must be a finite number, at least 1"
+                );
+            }
+            "max-delay" => {
+                self.max_delay =
+                    Duration::from_millis(value.parse().expect("max-delay must be in ms"))
+            }
+            "respawn-on" => {
+                self.respawn_on = match value {
+                    "close" => RespawnOn::Close,
+                    "abnormal" => RespawnOn::Abnormal,
+                    other => panic!("unknown respawn-on value: {}", other),
+                }
+            }
+            "placement" => {
+                self.placement = match value {
+                    "same" => Placement::Same,
+                    "cascade" => Placement::Cascade,
+                    other => panic!("unknown placement: {}", other),
+                }
+            }
+            "max-heads" => self.max_heads = value.parse().expect("max-heads must be a number"),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn load(&mut self, path: &str) {
+        let contents = std::fs::read_to_string(path).expect("can't read config file");
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .expect("config line must be key = value");
+
+            if !self.set(key.trim(), value.trim()) {
+                panic!("unknown config key: {}", key.trim());
+            }
+        }
+    }
+
+    pub fn to_args(&self) -> Vec<String> {
+        let respawn_on = match self.respawn_on {
+            RespawnOn::Close => "close",
+            RespawnOn::Abnormal => "abnormal",
+        };
+
+        let placement = match self.placement {
+            Placement::Same => "same",
+            Placement::Cascade => "cascade",
+        };
+
+        vec![
+            "--heads".to_string(),
+            self.heads.to_string(),
+            "--delay".to_string(),
+            self.delay.as_millis().to_string(),
+            "--backoff".to_string(),
+            self.backoff.to_string(),
+            "--max-delay".to_string(),
+            self.max_delay.as_millis().to_string(),
+            "--respawn-on".to_string(),
+            respawn_on.to_string(),
+            "--placement".to_string(),
+            placement.to_string(),
+            "--max-heads".to_string(),
+            self.max_heads.to_string(),
+        ]
+    }
+
+    pub fn should_respawn(&self, abnormal: bool) -> bool {
+        self.respawn_on == RespawnOn::Close || abnormal
+    }
+
+    pub fn delay_for(&self, generation: u32) -> Duration {
+        // No delay stays no delay, however far the factor has grown; 0 times
+        // an overflowed factor would be NaN.
+        if self.delay.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let factor = self.backoff.powi(generation.min(i32::MAX as u32) as i32);
+        let delay = self.delay.as_secs_f64() * factor;
+
+        if delay.is_finite() && delay < self.max_delay.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max_delay
+        }
+    }
+
+    pub fn position_for(&self, parent: (i32, i32), index: u32) -> (i32, i32) {
+        match self.placement {
+            Placement::Same => parent,
+            Placement::Cascade => {
+                let offset = CASCADE_STEP * (index as i32 + 1);
+                (parent.0 + offset, parent.1 + offset)
+            }
+        }
+    }
+}
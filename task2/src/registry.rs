@@ -0,0 +1,246 @@
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::policy::Policy;
+use crate::Head;
+
+const HOME_VAR: &str = "GORYNYCH_HOME";
+const STALE_LOCK: Duration = Duration::from_secs(2);
+
+pub fn home() -> PathBuf {
+    std::env::var_os(HOME_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("gorynych"))
+}
+
+fn heads_dir() -> PathBuf {
+    home().join("heads")
+}
+
+pub struct Entry {
+    pub pid: u32,
+    pub generation: u32,
+    pub parent: u32,
+    pub text: String,
+}
+
+struct Lock(PathBuf);
+
+impl Lock {
+    fn acquire() -> Self {
+        let path = home().join("lock");
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Self(path),
+                Err(_) => {
+                    // A head that died while holding the lock would block
+                    // everyone else forever.
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .map(|modified| modified.elapsed().unwrap_or_default() > STALE_LOCK)
+                        .unwrap_or(false);
+
+                    if stale {
+                        _ = std::fs::remove_file(&path);
+                    } else {
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(unix)]
+pub fn alive(pid: u32) -> bool {
+    let exists = unsafe {
+        libc::kill(pid as _, 0) == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    };
+
+    // Unreaped heads still answer to kill(0), but they are gone as far as the
+    // population is concerned.
+    let zombie = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| {
+            let state = stat.rsplit_once(')')?.1.trim_start().chars().next()?;
+            Some(state == 'Z' || state == 'X')
+        })
+        .unwrap_or(false);
+
+    exists && !zombie
+}
+
+#[cfg(windows)]
+pub fn alive(pid: u32) -> bool {
+    use windows::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return false;
+        };
+
+        let mut code = 0;
+        let res = GetExitCodeProcess(process, &mut code);
+        CloseHandle(process);
+
+        res.as_bool() && code == STILL_ACTIVE.0 as u32
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn parent_of(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // The name in parentheses may contain anything, so fields are counted
+    // from the last parenthesis: the state, then the parent.
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn parent_of(pid: u32) -> Option<u32> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "ppid=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+#[cfg(windows)]
+pub fn parent_of(pid: u32) -> Option<u32> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).ok()?;
+
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut parent = None;
+        let mut more = Process32FirstW(snapshot, &mut entry).as_bool();
+
+        while more {
+            if entry.th32ProcessID == pid {
+                parent = Some(entry.th32ParentProcessID);
+                break;
+            }
+            more = Process32NextW(snapshot, &mut entry).as_bool();
+        }
+
+        CloseHandle(snapshot);
+        parent
+    }
+}
+
+fn read(pid: u32) -> Option<Entry> {
+    let contents = std::fs::read_to_string(heads_dir().join(pid.to_string())).ok()?;
+    let (header, text) = contents.split_once('\n')?;
+    let (generation, parent) = header.split_once(':')?;
+
+    Some(Entry {
+        pid,
+        generation: generation.parse().ok()?,
+        parent: parent.parse().ok()?,
+        text: text.to_string(),
+    })
+}
+
+fn write(head: &Head, text: &str) {
+    _ = std::fs::write(
+        heads_dir().join(std::process::id().to_string()),
+        format!("{}:{}\n{}", head.generation, head.parent, text),
+    );
+}
+
+// Lists registered heads, dropping entries of heads that died without
+// unregistering.
+fn collect() -> Vec<Entry> {
+    let Ok(dir) = std::fs::read_dir(heads_dir()) else {
+        return vec![];
+    };
+
+    let mut entries = vec![];
+
+    for file in dir.flatten() {
+        let Some(pid) = file.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+
+        if alive(pid) {
+            if let Some(entry) = read(pid) {
+                entries.push(entry);
+            }
+        } else {
+            _ = std::fs::remove_file(file.path());
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.pid);
+    entries
+}
+
+// The entry of one head, also of one that died without unregistering as long
+// as nobody has listed the heads since.
+pub fn entry(pid: u32) -> Option<Entry> {
+    let _lock = Lock::acquire();
+    read(pid)
+}
+
+pub fn live() -> Vec<Entry> {
+    let _lock = Lock::acquire();
+    collect()
+}
+
+// Returns false when the population cap is already reached; the caller is
+// expected to exit without showing anything.
+pub fn register(head: &Head, policy: &Policy, text: &str) -> bool {
+    _ = std::fs::create_dir_all(heads_dir());
+
+    let _lock = Lock::acquire();
+
+    if policy.max_heads != 0 && collect().len() >= policy.max_heads as usize {
+        return false;
+    }
+
+    write(head, text);
+    true
+}
+
+pub fn update(head: &Head, text: &str) {
+    write(head, text);
+}
+
+pub fn unregister() {
+    _ = std::fs::remove_file(heads_dir().join(std::process::id().to_string()));
+}
+
+// How many successors may still be spawned without breaking the cap.
+pub fn room(policy: &Policy) -> u32 {
+    if policy.max_heads == 0 {
+        return u32::MAX;
+    }
+
+    policy.max_heads.saturating_sub(live().len() as u32)
+}
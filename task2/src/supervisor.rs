@@ -0,0 +1,723 @@
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+
+use crate::policy::Policy;
+use crate::{registry, trace, Head};
+
+const TICK: Duration = Duration::from_millis(50);
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+// How far up the parents of a registering head are followed.
+const MAX_ANCESTORS: usize = 64;
+
+// Supervisors leave their control sockets here, named after their pid.
+fn control_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("gorynych"),
+        None => registry::home().join("control"),
+    }
+}
+
+fn control_path(pid: u32) -> PathBuf {
+    control_dir().join(format!("supervisor-{}", pid))
+}
+
+// The control socket of this supervisor. It is only reachable by the user
+// running it: on unix it's a socket only they can open, on Windows a loopback
+// port that wants a token only they can read first.
+struct Control {
+    listener: Listener,
+    path: PathBuf,
+    #[cfg(windows)]
+    token: String,
+}
+
+impl Control {
+    #[cfg(unix)]
+    fn open() -> Self {
+        use std::fs::{DirBuilder, Permissions};
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        let dir = control_dir();
+        _ = DirBuilder::new().recursive(true).mode(0o700).create(&dir);
+
+        // Fails unless the directory is ours.
+        std::fs::set_permissions(&dir, Permissions::from_mode(0o700))
+            .expect("can't protect control directory");
+
+        let path = control_path(std::process::id());
+        _ = std::fs::remove_file(&path);
+
+        let listener = Listener::bind(&path).expect("can't bind control socket");
+        std::fs::set_permissions(&path, Permissions::from_mode(0o600))
+            .expect("can't protect control socket");
+
+        Self { listener, path }
+    }
+
+    #[cfg(windows)]
+    fn open() -> Self {
+        let listener = Listener::bind("127.0.0.1:0").expect("can't bind control socket");
+        let port = listener
+            .local_addr()
+            .expect("can't get socket address")
+            .port();
+
+        let token: String = random_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        // The temporary directory belongs to the user, and so does the file.
+        let path = control_path(std::process::id());
+        _ = std::fs::create_dir_all(control_dir());
+        std::fs::write(&path, format!("{} {}", port, token)).expect("can't write control file");
+
+        Self {
+            listener,
+            path,
+            token,
+        }
+    }
+
+    fn accept(&self) -> Option<Stream> {
+        let (stream, _) = self.listener.accept().ok()?;
+        stream.set_nonblocking(true).ok()?;
+        Some(stream)
+    }
+
+    // The message without whatever proves who sent it, if it does.
+    #[cfg(unix)]
+    fn check<'a>(&self, message: &'a str) -> Option<&'a str> {
+        Some(message)
+    }
+
+    #[cfg(windows)]
+    fn check<'a>(&self, message: &'a str) -> Option<&'a str> {
+        let (token, message) = message.split_once('\n')?;
+        (token == self.token).then_some(message)
+    }
+}
+
+// 128 bits from the system's random number generator, RtlGenRandom, which
+// only goes by its export name.
+#[cfg(windows)]
+fn random_bytes() -> [u8; 16] {
+    #[link(name = "advapi32")]
+    extern "system" {
+        #[link_name = "SystemFunction036"]
+        fn RtlGenRandom(buffer: *mut u8, length: u32) -> u8;
+    }
+
+    let mut bytes = [0; 16];
+    let filled = unsafe { RtlGenRandom(bytes.as_mut_ptr(), bytes.len() as u32) } != 0;
+    assert!(filled, "can't get random bytes for the control token");
+    bytes
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn connect(pid: u32) -> Option<Stream> {
+    Stream::connect(control_path(pid)).ok()
+}
+
+#[cfg(windows)]
+fn connect(pid: u32) -> Option<Stream> {
+    let contents = std::fs::read_to_string(control_path(pid)).ok()?;
+    let (port, token) = contents.split_once(' ')?;
+    let address: std::net::SocketAddr = ([127, 0, 0, 1], port.parse().ok()?).into();
+
+    let mut stream = Stream::connect_timeout(&address, IO_TIMEOUT).ok()?;
+    writeln!(stream, "{}", token).ok()?;
+    Some(stream)
+}
+
+fn send(pid: u32, message: &str) -> Option<String> {
+    let mut stream = connect(pid)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).ok()?;
+
+    stream.write_all(message.as_bytes()).ok()?;
+    stream.shutdown(Shutdown::Write).ok()?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).ok()?;
+    Some(reply)
+}
+
+// The pid of the only supervisor running, for when none is named.
+fn only_supervisor() -> u32 {
+    let pids: Vec<u32> = std::fs::read_dir(control_dir())
+        .map(|dir| {
+            dir.flatten()
+                .filter_map(|file| {
+                    let name = file.file_name().into_string().ok()?;
+                    name.strip_prefix("supervisor-")?.parse().ok()
+                })
+                .filter(|&pid| registry::alive(pid))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match pids[..] {
+        [pid] => pid,
+        [] => panic!("no supervisor is running"),
+        _ => panic!("several supervisors are running, pick one with --supervisor <pid>"),
+    }
+}
+
+pub fn register(head: &Head) {
+    if let Some(supervisor) = head.supervisor {
+        send(
+            supervisor,
+            &format!(
+                "regi:{}:{}:{}",
+                std::process::id(),
+                head.generation,
+                head.parent
+            ),
+        );
+    }
+}
+
+// Hands the respawn over to the supervisor. Returns false when the head is
+// not supervised or the supervisor is gone, so the head has to do it itself.
+pub fn request_respawn(
+    head: &Head,
+    abnormal: bool,
+    position: Option<(i32, i32)>,
+    text: &str,
+) -> bool {
+    let Some(supervisor) = head.supervisor else {
+        return false;
+    };
+
+    let position = match position {
+        Some((x, y)) => format!("{},{}", x, y),
+        None => "-".to_string(),
+    };
+
+    let message = format!(
+        "respawn:{}:{}:{}:{}:{}",
+        std::process::id(),
+        head.generation,
+        abnormal as u8,
+        position,
+        text
+    );
+
+    send(supervisor, &message).is_some()
+}
+
+pub fn command(mut args: impl Iterator<Item = String>) {
+    let mut supervisor = None;
+    let mut words = vec![];
+
+    while let Some(arg) = args.next() {
+        if arg == "--supervisor" {
+            supervisor = Some(
+                args.next()
+                    .and_then(|value| value.parse().ok())
+                    .expect("--supervisor needs a pid"),
+            );
+        } else {
+            words.push(arg);
+        }
+    }
+
+    let supervisor = supervisor.unwrap_or_else(only_supervisor);
+
+    match send(supervisor, &words.join(":")) {
+        Some(reply) => print!("{}", reply),
+        None => panic!("can't reach supervisor {}", supervisor),
+    }
+}
+
+#[derive(PartialEq)]
+enum State {
+    Running,
+    Respawning,
+    Stopping,
+    Exited(String),
+}
+
+struct Managed {
+    pid: u32,
+    generation: u32,
+    parent: u32,
+    text: String,
+    // Heads that registered on their own can't be reaped, only watched.
+    child: Option<Child>,
+    state: State,
+}
+
+struct Spawn {
+    due: Instant,
+    generation: u32,
+    parent: u32,
+    position: Option<(i32, i32)>,
+    text: String,
+}
+
+struct Supervisor {
+    template: Head,
+    policy: Policy,
+    heads: Vec<Managed>,
+    pending: Vec<Spawn>,
+    running: bool,
+}
+
+impl Supervisor {
+    fn find(&mut self, pid: u32) -> Option<&mut Managed> {
+        self.heads.iter_mut().find(|head| head.pid == pid)
+    }
+
+    // Whether `pid` descends from the supervisor or one of its heads. Heads
+    // left on their own spawn successors without asking, but the parents of
+    // those still lead back here.
+    fn owns(&self, pid: u32) -> bool {
+        let mut current = pid;
+
+        for _ in 0..MAX_ANCESTORS {
+            let Some(parent) = registry::parent_of(current) else {
+                return false;
+            };
+
+            if parent == std::process::id() || self.heads.iter().any(|head| head.pid == parent) {
+                return true;
+            }
+
+            current = parent;
+        }
+
+        false
+    }
+
+    fn spawn(&mut self, spawn: Spawn) {
+        let head = Head {
+            generation: spawn.generation,
+            ..self.template
+        };
+
+        let program = std::env::current_exe().expect("can't find own executable");
+
+        let mut args = head.child_args(spawn.parent, &self.policy, spawn.position);
+        args.push("--".to_string());
+        args.push(spawn.text.clone());
+
+        // A head with an editor gets a tmux pane, whose server owns it; it can
+        // only be watched, like a head that registered on its own.
+        #[cfg(unix)]
+        if !head.headless {
+            match crate::terminal::open(&crate::terminal::Display::Tmux, &program, &args) {
+                Some(pid) => self.heads.push(Managed {
+                    pid,
+                    generation: spawn.generation + 1,
+                    parent: spawn.parent,
+                    text: spawn.text,
+                    child: None,
+                    state: State::Running,
+                }),
+                None => eprintln!("can't open a tmux pane for a head"),
+            }
+            return;
+        }
+
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        match child {
+            Ok(child) => self.heads.push(Managed {
+                pid: child.id(),
+                generation: spawn.generation + 1,
+                parent: spawn.parent,
+                text: spawn.text,
+                child: Some(child),
+                state: State::Running,
+            }),
+            Err(error) => eprintln!("can't spawn head: {}", error),
+        }
+    }
+
+    fn schedule(
+        &mut self,
+        pid: u32,
+        generation: u32,
+        abnormal: bool,
+        position: Option<(i32, i32)>,
+        text: String,
+    ) {
+        let head = Head {
+            generation,
+            ..self.template
+        };
+
+        let delay = self.policy.delay_for(generation);
+        let mut due = Instant::now();
+
+        for i in 0..head.successors(&self.policy, abnormal) {
+            due += delay;
+
+            self.pending.push(Spawn {
+                due,
+                generation,
+                parent: pid,
+                position: position.map(|position| self.policy.position_for(position, i)),
+                text: text.clone(),
+            });
+        }
+    }
+
+    fn list(&self) -> String {
+        let mut reply = String::new();
+
+        for head in &self.heads {
+            let state = match &head.state {
+                State::Running => "running".to_string(),
+                State::Respawning => "respawning".to_string(),
+                State::Stopping => "stopping".to_string(),
+                State::Exited(status) => format!("exited ({})", status),
+            };
+
+            _ = writeln!(
+                reply,
+                "{}\t{}\t{}\t{}\t{:?}",
+                head.pid, head.generation, head.parent, state, head.text
+            );
+        }
+
+        reply
+    }
+
+    fn stop(&mut self, pid: u32) -> String {
+        let Some(head) = self.find(pid) else {
+            return format!("error: no head {}\n", pid);
+        };
+
+        if head.state != State::Running {
+            return format!("error: head {} is not running\n", pid);
+        }
+
+        head.state = State::Stopping;
+
+        match &mut head.child {
+            Some(child) => _ = child.kill(),
+            None => signal(pid, "9"),
+        }
+
+        "ok\n".to_string()
+    }
+
+    fn handle(&mut self, message: &str) -> String {
+        let mut fields = message.splitn(6, ':');
+
+        match fields.next().unwrap_or_default() {
+            "regi" => {
+                let (Some(pid), Some(generation), Some(parent)) = (
+                    fields.next().and_then(|pid| pid.parse().ok()),
+                    fields.next().and_then(|generation| generation.parse().ok()),
+                    fields.next().and_then(|parent| parent.parse().ok()),
+                ) else {
+                    return "error: bad registration\n".to_string();
+                };
+
+                if self.find(pid).is_none() {
+                    if !self.owns(pid) {
+                        return format!("error: {} is not a head of this supervisor\n", pid);
+                    }
+
+                    self.heads.push(Managed {
+                        pid,
+                        generation,
+                        parent,
+                        text: registry::entry(pid)
+                            .map(|entry| entry.text)
+                            .unwrap_or_default(),
+                        child: None,
+                        state: State::Running,
+                    });
+                }
+
+                "ok\n".to_string()
+            }
+            "respawn" => {
+                let (Some(pid), Some(generation), Some(abnormal), Some(position), Some(text)) = (
+                    fields.next().and_then(|pid| pid.parse().ok()),
+                    fields.next().and_then(|generation| generation.parse().ok()),
+                    fields.next().map(|abnormal| abnormal == "1"),
+                    fields.next(),
+                    fields.next(),
+                ) else {
+                    return "error: bad respawn request\n".to_string();
+                };
+
+                let position = position
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)));
+
+                // A stopped head is not supposed to come back.
+                match self.find(pid) {
+                    Some(head) if head.state != State::Running => return "stopped\n".to_string(),
+                    Some(head) => {
+                        head.state = State::Respawning;
+                        head.text = text.to_string();
+                    }
+                    None => {}
+                }
+
+                self.schedule(pid, generation, abnormal, position, text.to_string());
+                "ok\n".to_string()
+            }
+            "list" => self.list(),
+            "stop" => match fields.next().and_then(|pid| pid.parse().ok()) {
+                Some(pid) => self.stop(pid),
+                None => "error: stop needs a pid\n".to_string(),
+            },
+            "signal" => match (
+                fields.next().and_then(|pid| pid.parse().ok()),
+                fields.next(),
+            ) {
+                // A head that is gone may have left its pid to anyone.
+                (Some(pid), Some(number))
+                    if self
+                        .find(pid)
+                        .is_some_and(|head| head.state == State::Running) =>
+                {
+                    signal(pid, number);
+                    "ok\n".to_string()
+                }
+                _ => "error: signal needs the pid of a running head and a signal number\n"
+                    .to_string(),
+            },
+            "shutdown" => {
+                for pid in self.heads.iter().map(|head| head.pid).collect::<Vec<_>>() {
+                    self.stop(pid);
+                }
+                self.running = false;
+                "ok\n".to_string()
+            }
+            other => format!("error: unknown command {:?}\n", other),
+        }
+    }
+
+    fn reap(&mut self) {
+        let mut crashed = vec![];
+
+        for head in &mut self.heads {
+            if matches!(head.state, State::Exited(_)) {
+                continue;
+            }
+
+            let status = match &mut head.child {
+                Some(child) => match child.try_wait() {
+                    Ok(Some(status)) => status,
+                    _ => continue,
+                },
+                None if registry::alive(head.pid) => continue,
+                None => {
+                    // Orphaned heads are handed to the supervisor, which has
+                    // to collect them.
+                    #[cfg(unix)]
+                    unsafe {
+                        libc::waitpid(head.pid as _, std::ptr::null_mut(), libc::WNOHANG);
+                    }
+
+                    // Without an exit code, a head that went away without
+                    // asking for a respawn is all there is to tell a crash by.
+                    if head.state == State::Running {
+                        trace::log_exit(head.pid);
+                        crashed.push((head.pid, head.generation, head.text.clone()));
+                    }
+
+                    head.state = State::Exited("unknown".to_string());
+                    continue;
+                }
+            };
+
+            // Killed heads never got to write their own exit record.
+            if !status.success() {
+                trace::log_exit(head.pid);
+
+                if head.state == State::Running {
+                    crashed.push((head.pid, head.generation, head.text.clone()));
+                }
+            }
+
+            head.state = State::Exited(status.to_string());
+        }
+
+        // A crash is an abnormal end like any other, so the policy decides
+        // how many successors it gets. The text is the last one the head
+        // registered, if it's still there.
+        for (pid, generation, text) in crashed {
+            let text = registry::entry(pid).map_or(text, |entry| entry.text);
+            self.schedule(pid, generation, true, None, text);
+        }
+    }
+
+    fn spawn_due(&mut self) {
+        let now = Instant::now();
+
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|spawn| spawn.due <= now);
+
+        self.pending = pending;
+
+        for spawn in due {
+            self.spawn(spawn);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, number: &str) {
+    if let Ok(number) = number.parse() {
+        unsafe {
+            libc::kill(pid as _, number);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn signal(pid: u32, _number: &str) {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    // There are no signals to deliver, so every one of them ends the process.
+    unsafe {
+        if let Ok(process) = OpenProcess(PROCESS_TERMINATE, false, pid) {
+            TerminateProcess(process, 1);
+            CloseHandle(process);
+        }
+    }
+}
+
+// A client of the control socket. Nobody gets to hold up the supervisor: what
+// a client sends is picked up as it arrives, and a client that says nothing
+// for too long is dropped.
+struct Connection {
+    stream: Stream,
+    message: Vec<u8>,
+    since: Instant,
+}
+
+impl Connection {
+    // Some(true) once the whole message is in, None if the client is gone.
+    fn read(&mut self) -> Option<bool> {
+        let mut buffer = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Some(true),
+                Ok(length) => self.message.extend_from_slice(&buffer[..length]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Some(false),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+    }
+
+    fn reply(&mut self, reply: &str) {
+        _ = self.stream.set_nonblocking(false);
+        _ = self.stream.set_write_timeout(Some(IO_TIMEOUT));
+        _ = self.stream.write_all(reply.as_bytes());
+    }
+}
+
+pub fn run(head: Head, policy: Policy, text: String) {
+    // Heads with an editor are shown in tmux panes, which tell the supervisor
+    // their pids.
+    #[cfg(unix)]
+    if !head.headless
+        && !matches!(
+            crate::terminal::display(),
+            Some(crate::terminal::Display::Tmux)
+        )
+    {
+        eprintln!(
+            "a supervisor shows its heads in tmux panes: run it inside tmux or with --headless"
+        );
+        std::process::exit(1);
+    }
+
+    let control = Control::open();
+    control
+        .listener
+        .set_nonblocking(true)
+        .expect("can't make control socket non-blocking");
+
+    // Heads whose parent exited are handed to the supervisor rather than to
+    // init, so they can still be told from strangers.
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1);
+    }
+
+    let mut supervisor = Supervisor {
+        template: Head {
+            supervisor: Some(std::process::id()),
+            ..head
+        },
+        policy,
+        heads: vec![],
+        pending: vec![],
+        running: true,
+    };
+
+    // The supervisor itself counts as generation 0 of the tree it owns.
+    supervisor.spawn(Spawn {
+        due: Instant::now(),
+        generation: head.generation,
+        parent: std::process::id(),
+        position: head.position,
+        text,
+    });
+
+    let mut connections: Vec<Connection> = vec![];
+
+    while supervisor.running {
+        while let Some(stream) = control.accept() {
+            connections.push(Connection {
+                stream,
+                message: vec![],
+                since: Instant::now(),
+            });
+        }
+
+        connections.retain_mut(|connection| match connection.read() {
+            Some(true) => {
+                let message = String::from_utf8_lossy(&connection.message);
+
+                if let Some(message) = control.check(&message) {
+                    let reply = supervisor.handle(message);
+                    connection.reply(&reply);
+                }
+                false
+            }
+            Some(false) => connection.since.elapsed() < IO_TIMEOUT,
+            None => false,
+        });
+
+        supervisor.reap();
+        supervisor.spawn_due();
+
+        std::thread::sleep(TICK);
+    }
+}
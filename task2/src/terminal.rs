@@ -0,0 +1,298 @@
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+use crate::policy::Policy;
+use crate::{registry, supervisor, Head};
+
+const CTRL_Q: u8 = 0x11;
+const BACKSPACE: u8 = 0x7f;
+const CTRL_H: u8 = 0x08;
+
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+fn install_handlers() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        // No SA_RESTART: a blocking read of the keyboard has to be
+        // interrupted so the editor can notice the signal.
+        for signal in [libc::SIGTERM, libc::SIGHUP, libc::SIGINT] {
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+// SIGTERM is how a terminal head gets closed from outside, just like Ctrl-Q;
+// anything else means the head was torn down.
+fn abnormal(signal: i32) -> bool {
+    signal != libc::SIGTERM
+}
+
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        unsafe {
+            let mut original = std::mem::zeroed();
+
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+
+            let mut raw = original;
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw);
+
+            Some(Self { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+        print!("\x1b[2J\x1b[H");
+        _ = std::io::stdout().flush();
+    }
+}
+
+fn draw(head: &Head, text: &str) {
+    let mut stdout = std::io::stdout().lock();
+
+    _ = write!(
+        stdout,
+        "\x1b[2J\x1b[H\x1b[7m Gorynych | generation {} | Ctrl-Q closes \x1b[0m\r\n{}",
+        head.generation,
+        text.replace('\n', "\r\n")
+    );
+    _ = stdout.flush();
+}
+
+// Returns whether the editor went away abnormally.
+fn edit(head: &Head, text: &mut String) -> bool {
+    let Some(_raw) = RawMode::enable() else {
+        return wait();
+    };
+
+    draw(head, text);
+
+    let mut stdin = std::io::stdin().lock();
+    let mut pending = vec![];
+    let mut byte = [0];
+
+    loop {
+        let signal = SIGNAL.load(Ordering::SeqCst);
+        if signal != 0 {
+            return abnormal(signal);
+        }
+
+        match stdin.read(&mut byte) {
+            Ok(0) => return true,
+            Ok(_) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => return true,
+        }
+
+        match byte[0] {
+            CTRL_Q => return false,
+            BACKSPACE | CTRL_H => {
+                text.pop();
+            }
+            b'\r' | b'\n' => text.push('\n'),
+            0x1b => {}
+            byte if byte < 0x20 => continue,
+            byte => {
+                pending.push(byte);
+
+                match std::str::from_utf8(&pending) {
+                    Ok(character) => {
+                        text.push_str(character);
+                        pending.clear();
+                    }
+                    Err(error) if error.error_len().is_some() => pending.clear(),
+                    Err(_) => continue,
+                }
+            }
+        }
+
+        registry::update(head, text);
+        draw(head, text);
+    }
+}
+
+// Headless heads only hold their text and wait to be closed.
+fn wait() -> bool {
+    loop {
+        let signal = SIGNAL.load(Ordering::SeqCst);
+        if signal != 0 {
+            return abnormal(signal);
+        }
+
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// Where a head that isn't headless shows its successors: every one of them
+// needs a terminal of its own.
+pub enum Display {
+    Tmux,
+    // A terminal emulator that takes the command to run after -e.
+    Emulator(OsString),
+}
+
+// Emulators tried in order when there is no $TERMINAL.
+const EMULATORS: [&str; 2] = ["x-terminal-emulator", "xterm"];
+
+pub fn display() -> Option<Display> {
+    if std::env::var_os("TMUX").is_some() {
+        return Some(Display::Tmux);
+    }
+
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        return None;
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let found = |name: &OsString| {
+        Path::new(name).is_absolute()
+            || std::env::split_paths(&path).any(|dir| dir.join(name).is_file())
+    };
+
+    std::env::var_os("TERMINAL")
+        .into_iter()
+        .chain(EMULATORS.map(OsString::from))
+        .find(found)
+        .map(Display::Emulator)
+}
+
+// Starts `program` in a terminal of its own. Returns the pid of what was
+// started, which in a tmux pane is the program itself.
+pub fn open(display: &Display, program: &Path, args: &[String]) -> Option<u32> {
+    match display {
+        Display::Tmux => {
+            // Panes get the environment of the tmux server, not ours, so the
+            // registry and the trace have to be passed on.
+            let environment = std::env::vars_os()
+                .filter(|(name, _)| name.to_string_lossy().starts_with("GORYNYCH_"))
+                .flat_map(|(name, value)| {
+                    let mut variable = name;
+                    variable.push("=");
+                    variable.push(value);
+                    [OsString::from("-e"), variable]
+                });
+
+            let output = Command::new("tmux")
+                .args(["split-window", "-d", "-P", "-F", "#{pane_pid}"])
+                .args(environment)
+                .arg("--")
+                .arg(program)
+                .args(args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())?;
+
+            _ = Command::new("tmux")
+                .args(["select-layout", "tiled"])
+                .status();
+
+            String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+        }
+        Display::Emulator(emulator) => {
+            let mut command = Command::new(emulator);
+            command
+                .arg("-e")
+                .arg(program)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+
+            detach(&mut command);
+            command.spawn().ok().map(|child| child.id())
+        }
+    }
+}
+
+// In a session of its own, the process outlives the head that started it.
+fn detach(command: &mut Command) {
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+// Starts a successor in a terminal of its own, or detached when it's
+// headless. Returns false when it couldn't be started.
+fn spawn(head: &Head, args: &[String]) -> bool {
+    let program = std::env::current_exe().expect("can't find own executable");
+
+    if !head.headless {
+        return display().is_some_and(|display| open(&display, &program, args).is_some());
+    }
+
+    // A headless successor needs no terminal, so it runs detached in its own
+    // session and keeps its text until closed.
+    let mut command = Command::new(&program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    detach(&mut command);
+    command.spawn().is_ok()
+}
+
+// The editor has given the terminal back by now, so the head can wait out the
+// delay before every successor right here.
+fn respawn(head: &Head, policy: &Policy, text: &str, abnormal: bool) {
+    registry::unregister();
+
+    if supervisor::request_respawn(head, abnormal, None, text) {
+        return;
+    }
+
+    let mut args = head.child_args(std::process::id(), policy, None);
+    args.push("--".to_string());
+    args.push(text.to_string());
+
+    for _ in 0..head.successors(policy, abnormal) {
+        std::thread::sleep(policy.delay_for(head.generation));
+
+        if !spawn(head, &args) {
+            eprintln!("can't start a successor, not respawning the rest");
+            return;
+        }
+    }
+}
+
+pub fn run(head: Head, policy: Policy, mut text: String) {
+    install_handlers();
+
+    let abnormal = if head.headless {
+        wait()
+    } else {
+        edit(&head, &mut text)
+    };
+
+    respawn(&head, &policy, &text, abnormal);
+}
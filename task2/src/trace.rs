@@ -0,0 +1,225 @@
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TRACE_VAR: &str = "GORYNYCH_TRACE";
+
+pub fn trace_path() -> PathBuf {
+    std::env::var_os(TRACE_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("gorynych.trace"))
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock is before unix epoch")
+        .as_millis()
+}
+
+// Every record is written with a single `write_all` on a file opened in
+// append mode, so lines from concurrently running heads don't interleave.
+fn append(record: String) {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(trace_path());
+
+    if let Ok(mut file) = file {
+        _ = file.write_all(record.as_bytes());
+    }
+}
+
+pub fn log_spawn(parent: u32, generation: u32) {
+    append(format!(
+        "spawn:{}:{}:{}:{}\n",
+        std::process::id(),
+        parent,
+        generation,
+        now()
+    ));
+}
+
+pub fn log_exit(pid: u32) {
+    append(format!("exit:{}:{}\n", pid, now()));
+}
+
+// Pids get reused, so a node is the head that ran under a pid from its spawn
+// record on. Nodes are kept in the order of the file, and a parent is always
+// the latest head with the parent pid that was traced before its child, which
+// also means the tree can't have cycles.
+struct Node {
+    pid: u32,
+    parent: u32,
+    generation: u32,
+    spawned: u128,
+    exited: Option<u128>,
+    children: Vec<usize>,
+    is_root: bool,
+}
+
+impl Node {
+    // Stays the same when a later head reuses the pid.
+    fn id(&self) -> String {
+        format!("{}@{}", self.pid, self.spawned)
+    }
+}
+
+fn latest(nodes: &[Node], pid: u32) -> Option<usize> {
+    nodes.iter().rposition(|node| node.pid == pid)
+}
+
+fn load(path: &Path) -> Vec<Node> {
+    let contents = std::fs::read_to_string(path).expect("can't read trace file");
+
+    let mut nodes: Vec<Node> = vec![];
+
+    for line in contents.lines() {
+        let fields: Vec<_> = line.split(':').collect();
+
+        match fields[..] {
+            ["spawn", pid, parent, generation, time] => {
+                let pid = pid.parse().expect("bad pid in trace");
+                let parent = parent.parse().expect("bad parent pid in trace");
+
+                let index = nodes.len();
+                let parent_index = latest(&nodes, parent).filter(|_| parent != pid);
+
+                nodes.push(Node {
+                    pid,
+                    parent,
+                    generation: generation.parse().expect("bad generation in trace"),
+                    spawned: time.parse().expect("bad spawn time in trace"),
+                    exited: None,
+                    children: vec![],
+                    is_root: parent_index.is_none(),
+                });
+
+                if let Some(parent_index) = parent_index {
+                    nodes[parent_index].children.push(index);
+                }
+            }
+            ["exit", pid, time] => {
+                let pid: u32 = pid.parse().expect("bad pid in trace");
+                if let Some(index) = latest(&nodes, pid) {
+                    let node = &mut nodes[index];
+                    node.exited = node
+                        .exited
+                        .or(Some(time.parse().expect("bad exit time in trace")));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    nodes
+}
+
+fn to_dot(nodes: &[Node]) -> String {
+    let start = nodes.iter().map(|node| node.spawned).min().unwrap_or(0);
+
+    let mut dot = String::from("digraph gorynych {\n    node [shape=box];\n");
+
+    for node in nodes {
+        let exited = match node.exited {
+            Some(time) => format!("exited +{}ms", time.saturating_sub(start)),
+            None => "alive".to_string(),
+        };
+
+        _ = writeln!(
+            dot,
+            "    \"{}\" [label=\"pid {}\\ngeneration {}\\nspawned +{}ms\\n{}\"];",
+            node.id(),
+            node.pid,
+            node.generation,
+            node.spawned - start,
+            exited
+        );
+    }
+
+    for node in nodes {
+        for &child in &node.children {
+            _ = writeln!(dot, "    \"{}\" -> \"{}\";", node.id(), nodes[child].id());
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn node_to_json(nodes: &[Node], index: usize, json: &mut String) {
+    let node = &nodes[index];
+
+    let exited = match node.exited {
+        Some(time) => time.to_string(),
+        None => "null".to_string(),
+    };
+
+    _ = write!(
+        json,
+        "{{\"pid\":{},\"ppid\":{},\"generation\":{},\"spawned\":{},\"exited\":{},\"children\":[",
+        node.pid, node.parent, node.generation, node.spawned, exited
+    );
+
+    for (i, &child) in node.children.iter().enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+        node_to_json(nodes, child, json);
+    }
+
+    json.push_str("]}");
+}
+
+fn to_json(nodes: &[Node]) -> String {
+    let mut json = String::from("[");
+
+    let roots = (0..nodes.len()).filter(|&index| nodes[index].is_root);
+
+    for (i, root) in roots.enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+        node_to_json(nodes, root, &mut json);
+    }
+
+    json.push_str("]\n");
+    json
+}
+
+pub fn command(mut args: impl Iterator<Item = String>) {
+    let mut format = "dot".to_string();
+    let mut file = trace_path();
+    let mut output = None;
+    let mut clear = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().expect("--format needs a value"),
+            "--file" => file = args.next().expect("--file needs a value").into(),
+            "--output" => output = Some(args.next().expect("--output needs a value")),
+            "--clear" => clear = true,
+            other => panic!("unknown trace argument: {}", other),
+        }
+    }
+
+    if clear {
+        _ = std::fs::remove_file(&file);
+        return;
+    }
+
+    let nodes = load(&file);
+
+    let rendered = match format.as_str() {
+        "dot" => to_dot(&nodes),
+        "json" => to_json(&nodes),
+        other => panic!("unknown trace format: {}", other),
+    };
+
+    match output {
+        Some(output) => std::fs::write(output, rendered).expect("can't write trace output"),
+        None => print!("{}", rendered),
+    }
+}
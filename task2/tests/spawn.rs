@@ -0,0 +1,388 @@
+#![cfg(unix)]
+
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static SESSIONS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug)]
+struct Head {
+    pid: u32,
+    generation: u32,
+    parent: u32,
+    text: String,
+}
+
+// One isolated Gorynych population. Every head it ever spawned is killed when
+// the session is dropped, including while a failed assertion unwinds.
+struct Session {
+    home: PathBuf,
+    root: Child,
+}
+
+impl Session {
+    fn start(args: &[&str]) -> Self {
+        let mut headless = vec!["--headless"];
+        headless.extend(args);
+
+        Self::spawn(&headless)
+    }
+
+    fn supervised(args: &[&str]) -> Self {
+        let mut supervise = vec!["supervise", "--headless"];
+        supervise.extend(args);
+
+        Self::spawn(&supervise)
+    }
+
+    fn spawn(args: &[&str]) -> Self {
+        let home = std::env::temp_dir().join(format!(
+            "gorynych-test-{}-{}",
+            std::process::id(),
+            SESSIONS.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).expect("can't create session directory");
+
+        let root = Self::command(&home)
+            .args(args)
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("can't start gorynych");
+
+        let session = Self { home, root };
+        session.wait_for(|heads| heads.len() == 1);
+        session
+    }
+
+    fn command(home: &PathBuf) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_task2"));
+        command
+            .env("GORYNYCH_HOME", home)
+            .env("GORYNYCH_TRACE", home.join("trace"))
+            // Control sockets go next to the registry.
+            .env_remove("XDG_RUNTIME_DIR");
+        command
+    }
+
+    fn root(&self) -> u32 {
+        self.root.id()
+    }
+
+    fn heads(&self) -> Vec<Head> {
+        let output = Self::command(&self.home)
+            .arg("heads")
+            .output()
+            .expect("can't list heads");
+
+        String::from_utf8(output.stdout)
+            .expect("heads output isn't utf-8")
+            .lines()
+            .map(|line| {
+                let fields: Vec<_> = line.splitn(4, '\t').collect();
+
+                Head {
+                    pid: fields[0].parse().unwrap(),
+                    generation: fields[1].parse().unwrap(),
+                    parent: fields[2].parse().unwrap(),
+                    text: fields[3].trim_matches('"').to_string(),
+                }
+            })
+            .collect()
+    }
+
+    fn wait_for(&self, condition: impl Fn(&[Head]) -> bool) -> Vec<Head> {
+        let start = Instant::now();
+
+        loop {
+            let heads = self.heads();
+
+            if condition(&heads) {
+                return heads;
+            }
+
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "timed out, population is {:?}",
+                heads
+            );
+
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn signal(&mut self, pid: u32, signal: i32) {
+        unsafe {
+            libc::kill(pid as _, signal);
+        }
+
+        if pid == self.root() {
+            _ = self.root.wait();
+        }
+    }
+
+    fn close(&mut self, pid: u32) {
+        self.signal(pid, libc::SIGTERM);
+    }
+
+    fn ctl(&self, command: &[&str]) -> String {
+        let output = Self::command(&self.home)
+            .args(["ctl", "--supervisor", &self.root().to_string()])
+            .args(command)
+            .output()
+            .expect("can't reach supervisor");
+
+        String::from_utf8(output.stdout).expect("ctl output isn't utf-8")
+    }
+
+    // Whether a live process is one of this session's heads rather than
+    // something that got the pid of a head since.
+    fn owns(&self, pid: u32) -> bool {
+        let Ok(environment) = std::fs::read(format!("/proc/{}/environ", pid)) else {
+            // Nothing to check against without /proc; the registry has to do.
+            return !std::path::Path::new("/proc/self").exists();
+        };
+
+        let home = format!("GORYNYCH_HOME={}", self.home.display());
+        environment
+            .split(|&byte| byte == 0)
+            .any(|variable| variable == home.as_bytes())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // The root leads its own process group, which takes everything it
+        // spawned without starting a session of its own along with it.
+        unsafe {
+            libc::killpg(self.root() as _, libc::SIGKILL);
+        }
+        _ = self.root.wait();
+
+        // Detached heads are in sessions of their own. SIGKILL never triggers
+        // a respawn, but a head caught in the middle of spawning can still
+        // start a few more, so sweep the registry until nothing is left.
+        for _ in 0..50 {
+            let pids: Vec<_> = self
+                .heads()
+                .into_iter()
+                .map(|head| head.pid)
+                .filter(|&pid| self.owns(pid))
+                .collect();
+
+            if pids.is_empty() {
+                break;
+            }
+
+            for pid in pids {
+                unsafe {
+                    libc::kill(pid as _, libc::SIGKILL);
+                }
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+#[test]
+fn close_spawns_configured_number_of_heads() {
+    let mut session = Session::start(&["--heads", "4"]);
+    let root = session.root();
+
+    session.close(root);
+
+    let heads = session.wait_for(|heads| heads.len() == 4);
+
+    for head in heads {
+        assert_eq!(head.parent, root);
+        assert_eq!(head.generation, 1);
+    }
+}
+
+#[test]
+fn successors_inherit_text() {
+    // Words that only look like flags are text too.
+    let mut session = Session::start(&["--heads", "2", "--three", "heads", "--", "are better"]);
+
+    session.close(session.root());
+
+    let heads = session.wait_for(|heads| heads.len() == 2);
+
+    for head in heads {
+        assert_eq!(head.text, "--three heads are better");
+    }
+}
+
+#[test]
+fn population_cap_holds() {
+    let mut session = Session::start(&["--heads", "3", "--max-heads", "5"]);
+
+    session.close(session.root());
+
+    let heads = session.wait_for(|heads| heads.len() == 3);
+
+    for head in &heads {
+        session.close(head.pid);
+    }
+
+    let start = Instant::now();
+    let mut peak = 0;
+
+    while start.elapsed() < Duration::from_secs(2) {
+        peak = peak.max(session.heads().len());
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(peak, 5);
+    assert_eq!(session.heads().len(), 5);
+}
+
+#[test]
+fn abnormal_policy_ignores_plain_close() {
+    let mut session = Session::start(&["--respawn-on", "abnormal"]);
+
+    session.close(session.root());
+    std::thread::sleep(Duration::from_millis(500));
+
+    assert!(session.heads().is_empty());
+}
+
+#[test]
+fn abnormal_policy_respawns_on_hangup() {
+    let mut session = Session::start(&["--heads", "2", "--respawn-on", "abnormal"]);
+    let root = session.root();
+
+    session.signal(root, libc::SIGHUP);
+
+    session.wait_for(|heads| heads.len() == 2 && heads.iter().all(|head| head.parent == root));
+}
+
+#[test]
+fn terminal_heads_are_refused_without_a_terminal_for_successors() {
+    let home = std::env::temp_dir().join(format!("gorynych-test-{}-terminal", std::process::id()));
+
+    let output = Session::command(&home)
+        .env_remove("TMUX")
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .arg("hello")
+        .stdin(Stdio::null())
+        .output()
+        .expect("can't start gorynych");
+
+    _ = std::fs::remove_dir_all(&home);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--headless"));
+}
+
+#[test]
+fn supervisor_refuses_terminal_heads_outside_tmux() {
+    let home = std::env::temp_dir().join(format!("gorynych-test-{}-supervise", std::process::id()));
+
+    let output = Session::command(&home)
+        .env_remove("TMUX")
+        .args(["supervise", "hello"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("can't start gorynych");
+
+    _ = std::fs::remove_dir_all(&home);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("tmux"));
+}
+
+#[test]
+fn supervisor_restarts_crashed_heads_but_not_stopped_ones() {
+    let mut session = Session::supervised(&["--heads", "3", "--max-heads", "2"]);
+
+    let crashed = session.heads()[0].pid;
+    session.signal(crashed, libc::SIGKILL);
+
+    // A crash fans out like any other abnormal end, as far as the cap allows.
+    let heads = session
+        .wait_for(|heads| heads.len() == 2 && heads.iter().all(|head| head.parent == crashed));
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(session.heads().len(), 2);
+
+    for head in &heads {
+        assert_eq!(session.ctl(&["stop", &head.pid.to_string()]), "ok\n");
+    }
+
+    session.wait_for(|heads| heads.is_empty());
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(session.heads().is_empty());
+
+    let list = session.ctl(&["list"]);
+    assert!(list.contains(&format!("{}\t1\t", crashed)));
+    assert!(list.contains("SIGKILL"));
+}
+
+#[test]
+fn supervisor_keeps_working_past_silent_clients_and_strangers() {
+    let session = Session::supervised(&[]);
+    let socket = session
+        .home
+        .join("control")
+        .join(format!("supervisor-{}", session.root()));
+
+    // A client that connects and never says anything.
+    let _silent = std::os::unix::net::UnixStream::connect(&socket).expect("can't connect");
+
+    let mut stranger = Command::new("sleep")
+        .arg("10")
+        .spawn()
+        .expect("can't start sleep");
+    let pid = stranger.id().to_string();
+
+    assert!(session.ctl(&["regi", &pid, "1", "1"]).starts_with("error"));
+    assert!(session.ctl(&["signal", &pid, "9"]).starts_with("error"));
+    assert!(stranger.try_wait().unwrap().is_none());
+
+    _ = stranger.kill();
+    _ = stranger.wait();
+}
+
+#[test]
+fn trace_tells_apart_heads_that_reused_a_pid() {
+    let path = std::env::temp_dir().join(format!("gorynych-test-{}-reuse", std::process::id()));
+
+    // Pid 100 exits and comes back as a grandchild of itself.
+    std::fs::write(
+        &path,
+        "spawn:100:0:0:1\nspawn:200:100:1:2\nexit:100:3\nspawn:100:200:2:4\nspawn:300:100:3:5\n",
+    )
+    .expect("can't write trace");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_task2"))
+        .args(["trace", "--format", "json", "--file"])
+        .arg(&path)
+        .output()
+        .expect("can't render trace");
+
+    _ = std::fs::remove_file(&path);
+
+    assert_eq!(
+        String::from_utf8(output.stdout).expect("trace isn't utf-8"),
+        concat!(
+            "[{\"pid\":100,\"ppid\":0,\"generation\":0,\"spawned\":1,\"exited\":3,\"children\":[",
+            "{\"pid\":200,\"ppid\":100,\"generation\":1,\"spawned\":2,\"exited\":null,\"children\":[",
+            "{\"pid\":100,\"ppid\":200,\"generation\":2,\"spawned\":4,\"exited\":null,\"children\":[",
+            "{\"pid\":300,\"ppid\":100,\"generation\":3,\"spawned\":5,\"exited\":null,\"children\":[",
+            "]}]}]}]}]\n"
+        )
+    );
+}
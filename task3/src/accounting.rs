@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+// What the OS knows about the calling thread. Counters a platform can't
+// provide stay `None` instead of pretending to be zero.
+#[derive(Default, Clone, Copy)]
+pub struct Usage {
+    pub user: Duration,
+    pub system: Duration,
+    pub voluntary: Option<u64>,
+    pub involuntary: Option<u64>,
+    pub run_delay: Option<Duration>,
+    pub cpu: Option<u32>,
+}
+
+impl Usage {
+    pub fn cpu_time(&self) -> Duration {
+        self.user + self.system
+    }
+
+    pub fn since(&self, start: &Self) -> Self {
+        let delta = |end: Option<u64>, start: Option<u64>| Some(end?.saturating_sub(start?));
+
+        Self {
+            user: self.user.saturating_sub(start.user),
+            system: self.system.saturating_sub(start.system),
+            voluntary: delta(self.voluntary, start.voluntary),
+            involuntary: delta(self.involuntary, start.involuntary),
+            run_delay: self
+                .run_delay
+                .zip(start.run_delay)
+                .map(|(end, start)| end.saturating_sub(start)),
+            cpu: self.cpu,
+        }
+    }
+
+    // Time spent runnable but not running. The scheduler tracks it exactly on
+    // Linux; elsewhere whatever wall time wasn't spent on a CPU is the best
+    // estimate, which also counts voluntary sleeping.
+    pub fn waiting(&self, wall: Duration) -> Duration {
+        self.run_delay
+            .unwrap_or_else(|| wall.saturating_sub(self.cpu_time()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn snapshot() -> Usage {
+    fn timeval(time: libc::timeval) -> Duration {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    }
+
+    let mut usage = Usage::default();
+
+    unsafe {
+        let mut rusage: libc::rusage = std::mem::zeroed();
+
+        if libc::getrusage(libc::RUSAGE_THREAD, &mut rusage) == 0 {
+            usage.user = timeval(rusage.ru_utime);
+            usage.system = timeval(rusage.ru_stime);
+            usage.voluntary = Some(rusage.ru_nvcsw as u64);
+            usage.involuntary = Some(rusage.ru_nivcsw as u64);
+        }
+    }
+
+    let task = format!("/proc/self/task/{}", unsafe { libc::gettid() });
+
+    // schedstat is "time on cpu, time waiting on a runqueue, timeslices" in ns.
+    usage.run_delay = std::fs::read_to_string(format!("{}/schedstat", task))
+        .ok()
+        .and_then(|schedstat| schedstat.split_whitespace().nth(1)?.parse().ok())
+        .map(Duration::from_nanos);
+
+    // Field 39 of stat is the CPU the thread last ran on. The command name
+    // before it may contain spaces, so count from the closing parenthesis.
+    usage.cpu = std::fs::read_to_string(format!("{}/stat", task))
+        .ok()
+        .and_then(|stat| {
+            stat.rsplit_once(')')?
+                .1
+                .split_whitespace()
+                .nth(36)?
+                .parse()
+                .ok()
+        });
+
+    usage
+}
+
+#[cfg(windows)]
+pub fn snapshot() -> Usage {
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::System::Threading::{GetCurrentThread, GetThreadTimes};
+
+    fn filetime(time: FILETIME) -> Duration {
+        let ticks = (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+        Duration::from_nanos(ticks * 100)
+    }
+
+    let mut usage = Usage::default();
+
+    unsafe {
+        let mut creation = FILETIME::default();
+        let mut exit = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+
+        if GetThreadTimes(
+            GetCurrentThread(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+        .as_bool()
+        {
+            usage.user = filetime(user);
+            usage.system = filetime(kernel);
+        }
+    }
+
+    usage.cpu = current_cpu();
+
+    usage
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn snapshot() -> Usage {
+    Usage::default()
+}
+
+#[cfg(target_os = "linux")]
+pub fn current_cpu() -> Option<u32> {
+    let cpu = unsafe { libc::sched_getcpu() };
+    (cpu >= 0).then_some(cpu as u32)
+}
+
+#[cfg(windows)]
+pub fn current_cpu() -> Option<u32> {
+    unsafe { Some(windows::Win32::System::Threading::GetCurrentProcessorNumber()) }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn current_cpu() -> Option<u32> {
+    None
+}
@@ -0,0 +1,87 @@
+pub fn parse(list: &str) -> Vec<usize> {
+    let mut cores = vec![];
+
+    for part in list.split(',') {
+        let bad = || panic!("bad core list: {}", list);
+
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.parse().unwrap_or_else(|_| bad());
+                let last: usize = last.parse().unwrap_or_else(|_| bad());
+                cores.extend(first..=last);
+            }
+            None => cores.push(part.parse().unwrap_or_else(|_| bad())),
+        }
+    }
+
+    cores.sort_unstable();
+    cores.dedup();
+    cores
+}
+
+pub fn available_cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+// Restricts the calling thread to `cores` and describes the result.
+#[cfg(target_os = "linux")]
+pub fn apply(cores: &[usize]) -> String {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+
+        for &core in cores {
+            libc::CPU_SET(core, &mut set);
+        }
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            format!("cores {:?}", cores)
+        } else {
+            format!(
+                "any core, sched_setaffinity failed: {}",
+                std::io::Error::last_os_error()
+            )
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn apply(cores: &[usize]) -> String {
+    use windows::Win32::System::Threading::{GetCurrentThread, SetThreadAffinityMask};
+
+    // Affinity masks only reach the first 64 cores of a processor group.
+    let mask = cores
+        .iter()
+        .filter(|&&core| core < usize::BITS as usize)
+        .fold(0usize, |mask, &core| mask | 1 << core);
+
+    unsafe {
+        if SetThreadAffinityMask(GetCurrentThread(), mask) != 0 {
+            format!("cores {:?}", cores)
+        } else {
+            "any core, SetThreadAffinityMask failed".to_string()
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn apply(_cores: &[usize]) -> String {
+    "any core, no affinity support on this platform".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lists_and_ranges() {
+        assert_eq!(parse("0"), [0]);
+        assert_eq!(parse("0,2-3"), [0, 2, 3]);
+        assert_eq!(parse("3-5,1,4"), [1, 3, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad core list")]
+    fn parse_rejects_garbage() {
+        parse("0,x");
+    }
+}
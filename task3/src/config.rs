@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use crate::affinity;
+use crate::inversion::Mitigation;
+use crate::numa::Binding;
+use crate::priority::Priority;
+use crate::schedule::Schedule;
+use crate::sort::Algorithm;
+use crate::workload::Kind;
+
+pub const USAGE: &str = "\
+usage: task3 [options]
+
+  --threads N          number of worker threads (default 8)
+  --elements N         elements per thread (default 10000000)
+  --priorities P       round-robin, same:PRIORITY or list:P1,P2,...
+                       (priorities are lowest, below-normal, above-normal,
+                       highest or 0..3; default round-robin)
+  --workload KIND      sort-random, sort-sorted, sort-reverse, sort-duplicates,
+                       alu, pointer-chase, hash, bandwidth, streaming reads
+                       reported in GB/s next to where the memory is, or sleep
+                       (default sort-reverse)
+  --algorithm A        how sort workloads sort: unstable (default), stable,
+                       radix, heap, merge or all to compare every one of them
+  --parts N            threads of the same priority a merge sort splits its
+                       array across (default 4)
+  --pin CORE           run every thread on a single core
+  --cores LIST         run every thread on a core set, e.g. 0,2-3
+  --numa NODE          allocate every thread's memory on NUMA node NODE, or
+                       spread to go round-robin over the nodes (default:
+                       wherever the worker first touches it)
+  --oversubscribe K    start K threads per available core, overriding --threads
+  --change MS:T:P      set thread T to priority P once MS ms into every run,
+                       e.g. 500:3:lowest (may be repeated)
+  --rotate MS          every MS ms shift each thread to its neighbour's
+                       priority
+  --boost MS           boost a thread that made no progress for MS ms to
+                       highest for a moment, like Windows does for starving
+                       threads
+  --curves FILE        write every thread's progress over time as CSV
+  --scenario S         independent (default) or inversion, where a lowest
+                       thread holds a lock a highest one needs while
+                       above-normal threads keep the core busy
+  --mitigation M       none, inherit, ceiling, lock-free or all (default all),
+                       what the inversion scenario compares
+  --warmup N           runs to do and discard before measuring (default 0)
+  --repeat N           measured runs (default 1)
+  --output FILE        write per-priority statistics to FILE
+  --format F           json or csv (default from the FILE extension, else json)
+  --trace FILE         write a Chrome trace / Perfetto timeline of every run
+";
+
+pub enum Pattern {
+    RoundRobin,
+    Same(Priority),
+    List(Vec<Priority>),
+}
+
+impl Pattern {
+    fn parse(value: &str) -> Self {
+        let parse_priority = |value: &str| {
+            Priority::parse(value).unwrap_or_else(|| panic!("bad priority: {}", value))
+        };
+
+        match value.split_once(':') {
+            None if value == "round-robin" => Self::RoundRobin,
+            Some(("same", priority)) => Self::Same(parse_priority(priority)),
+            Some(("list", list)) => Self::List(list.split(',').map(parse_priority).collect()),
+            _ => panic!("bad priority pattern: {}", value),
+        }
+    }
+
+    pub fn priority_for(&self, thread_num: usize) -> Priority {
+        match self {
+            Self::RoundRobin => Priority::from_index(thread_num % Priority::ALL.len()),
+            Self::Same(priority) => *priority,
+            Self::List(list) => list[thread_num % list.len()],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    Independent,
+    Inversion,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+pub struct Config {
+    pub threads: usize,
+    pub elements: usize,
+    pub pattern: Pattern,
+    pub workload: Kind,
+    pub algorithms: Vec<Algorithm>,
+    pub parts: usize,
+    pub cores: Option<Vec<usize>>,
+    pub numa: Option<Binding>,
+    pub schedule: Schedule,
+    pub scenario: Scenario,
+    pub mitigations: Vec<Mitigation>,
+    pub warmup: usize,
+    pub repeat: usize,
+    pub output: Option<String>,
+    pub format: Option<Format>,
+    pub trace: Option<String>,
+    pub curves: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threads: 8,
+            elements: 10_000_000,
+            pattern: Pattern::RoundRobin,
+            workload: Kind::SortReverse,
+            algorithms: vec![Algorithm::Unstable],
+            parts: 4,
+            cores: None,
+            numa: None,
+            schedule: Schedule::default(),
+            scenario: Scenario::Independent,
+            mitigations: Mitigation::ALL.to_vec(),
+            warmup: 0,
+            repeat: 1,
+            output: None,
+            format: None,
+            trace: None,
+            curves: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut config = Self::default();
+        let mut oversubscribe = None;
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .unwrap_or_else(|| panic!("{} needs a value\n{}", arg, USAGE));
+
+            let number = || -> usize {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{} needs a number", arg))
+            };
+
+            match arg.as_str() {
+                "--threads" => config.threads = number(),
+                "--elements" => config.elements = number(),
+                "--priorities" => config.pattern = Pattern::parse(&value),
+                "--workload" => {
+                    config.workload = Kind::parse(&value)
+                        .unwrap_or_else(|| panic!("unknown workload: {}\n{}", value, USAGE))
+                }
+                "--algorithm" => {
+                    config.algorithms = match value.as_str() {
+                        "all" => Algorithm::ALL.to_vec(),
+                        other => vec![Algorithm::parse(other)
+                            .unwrap_or_else(|| panic!("unknown algorithm: {}\n{}", other, USAGE))],
+                    }
+                }
+                "--parts" => config.parts = number(),
+                "--pin" => config.cores = Some(vec![number()]),
+                "--cores" => config.cores = Some(affinity::parse(&value)),
+                "--numa" => config.numa = Some(Binding::parse(&value)),
+                "--oversubscribe" => oversubscribe = Some(number()),
+                "--change" => config.schedule.changes.push(Schedule::parse_change(&value)),
+                "--rotate" => config.schedule.rotate = Some(Duration::from_millis(number() as u64)),
+                "--boost" => config.schedule.boost = Some(Duration::from_millis(number() as u64)),
+                "--curves" => config.curves = Some(value),
+                "--scenario" => {
+                    config.scenario = match value.as_str() {
+                        "independent" => Scenario::Independent,
+                        "inversion" => Scenario::Inversion,
+                        other => panic!("unknown scenario: {}\n{}", other, USAGE),
+                    }
+                }
+                "--mitigation" => {
+                    config.mitigations = match value.as_str() {
+                        "all" => Mitigation::ALL.to_vec(),
+                        other => vec![Mitigation::parse(other)
+                            .unwrap_or_else(|| panic!("unknown mitigation: {}\n{}", other, USAGE))],
+                    }
+                }
+                "--warmup" => config.warmup = number(),
+                "--repeat" => config.repeat = number(),
+                "--output" => config.output = Some(value),
+                "--trace" => config.trace = Some(value),
+                "--format" => {
+                    config.format = match value.as_str() {
+                        "json" => Some(Format::Json),
+                        "csv" => Some(Format::Csv),
+                        other => panic!("unknown format: {}", other),
+                    }
+                }
+                other => panic!("unknown argument: {}\n{}", other, USAGE),
+            }
+        }
+
+        // Priorities only matter when threads compete for a core, so size the
+        // pool against the cores the threads may actually use.
+        if let Some(factor) = oversubscribe {
+            let cores = match &config.cores {
+                Some(cores) => cores.len(),
+                None => affinity::available_cores(),
+            };
+
+            config.threads = factor * cores;
+        }
+
+        assert!(config.threads > 0, "--threads can't be 0");
+        assert!(config.repeat > 0, "--repeat can't be 0");
+        assert!(config.parts > 0, "--parts can't be 0");
+
+        // Threads are only known once --oversubscribe is resolved.
+        if let Some((_, thread, _)) = config
+            .schedule
+            .changes
+            .iter()
+            .find(|(_, thread, _)| *thread >= config.threads)
+        {
+            panic!(
+                "--change names thread {}, but threads go from 0 to {}\n{}",
+                thread,
+                config.threads - 1,
+                USAGE
+            );
+        }
+
+        if config.format.is_none() {
+            if let Some(output) = &config.output {
+                config.format = Some(if output.ends_with(".csv") {
+                    Format::Csv
+                } else {
+                    Format::Json
+                });
+            }
+        }
+
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_parse() {
+        let round_robin = Pattern::parse("round-robin");
+        assert_eq!(round_robin.priority_for(0), Priority::Lowest);
+        assert_eq!(round_robin.priority_for(5), Priority::BelowNormal);
+
+        let same = Pattern::parse("same:highest");
+        assert_eq!(same.priority_for(7), Priority::Highest);
+
+        let list = Pattern::parse("list:0,above-normal");
+        assert_eq!(list.priority_for(0), Priority::Lowest);
+        assert_eq!(list.priority_for(1), Priority::AboveNormal);
+        assert_eq!(list.priority_for(2), Priority::Lowest);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad priority")]
+    fn pattern_rejects_unknown_priority() {
+        Pattern::parse("same:urgent");
+    }
+
+    #[test]
+    #[should_panic(expected = "bad priority pattern")]
+    fn pattern_rejects_unknown_pattern() {
+        Pattern::parse("shuffle");
+    }
+
+    #[test]
+    #[should_panic(expected = "--change names thread 4")]
+    fn change_beyond_last_thread_is_rejected() {
+        let args = ["--threads", "4", "--change", "500:4:lowest"];
+        Config::parse(args.into_iter().map(String::from));
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt::Write as _;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Barrier, OnceLock};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::priority::{self, Priority};
+use crate::scope;
+use crate::workload;
+
+// How much longer than the critical section every medium thread keeps the
+// core busy.
+const MEDIUM_WORK: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mitigation {
+    None,
+    Inherit,
+    Ceiling,
+    LockFree,
+}
+
+impl Mitigation {
+    pub const ALL: [Self; 4] = [Self::None, Self::Inherit, Self::Ceiling, Self::LockFree];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Inherit => "inherit",
+            Self::Ceiling => "ceiling",
+            Self::LockFree => "lock-free",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mitigation| mitigation.name() == value)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod lock {
+    use std::cell::UnsafeCell;
+
+    // A raw pthread mutex, since std doesn't let us choose the protocol. It is
+    // boxed because a pthread mutex must not move once initialized.
+    pub struct Lock(Box<UnsafeCell<libc::pthread_mutex_t>>);
+
+    unsafe impl Send for Lock {}
+    unsafe impl Sync for Lock {}
+
+    impl Lock {
+        pub fn new(inherit: bool) -> (Self, String) {
+            let lock = Self(Box::new(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER)));
+
+            let protocol = if inherit {
+                libc::PTHREAD_PRIO_INHERIT
+            } else {
+                libc::PTHREAD_PRIO_NONE
+            };
+
+            unsafe {
+                let mut attr: libc::pthread_mutexattr_t = std::mem::zeroed();
+                libc::pthread_mutexattr_init(&mut attr);
+
+                let granted = libc::pthread_mutexattr_setprotocol(&mut attr, protocol) == 0
+                    && libc::pthread_mutex_init(lock.0.get(), &attr) == 0;
+
+                libc::pthread_mutexattr_destroy(&mut attr);
+
+                let description = match (inherit, granted) {
+                    (true, true) => "PTHREAD_PRIO_INHERIT",
+                    (false, true) => "PTHREAD_PRIO_NONE",
+                    (_, false) => "default mutex, pthread_mutexattr_setprotocol failed",
+                };
+
+                (lock, description.to_string())
+            }
+        }
+
+        pub fn with<T>(&self, critical: impl FnOnce() -> T) -> T {
+            unsafe {
+                libc::pthread_mutex_lock(self.0.get());
+                let result = critical();
+                libc::pthread_mutex_unlock(self.0.get());
+                result
+            }
+        }
+    }
+
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            unsafe {
+                libc::pthread_mutex_destroy(self.0.get());
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod lock {
+    use std::sync::Mutex;
+
+    // Without a way to pick the protocol every lock is a plain one.
+    pub struct Lock(Mutex<()>);
+
+    impl Lock {
+        pub fn new(inherit: bool) -> (Self, String) {
+            let description = if inherit {
+                "plain mutex, no priority inheritance on this platform"
+            } else {
+                "plain mutex"
+            };
+
+            (Self(Mutex::new(())), description.to_string())
+        }
+
+        pub fn with<T>(&self, critical: impl FnOnce() -> T) -> T {
+            let _guard = self.0.lock().unwrap_or_else(|poison| poison.into_inner());
+            critical()
+        }
+    }
+}
+
+use lock::Lock;
+
+struct Outcome {
+    wait: Duration,
+    hold: Duration,
+    medium: Duration,
+}
+
+fn joined<T>(result: std::thread::Result<T>) -> T {
+    result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn critical_section(elements: usize, seed: u64) -> u64 {
+    black_box(workload::alu(elements, seed))
+}
+
+// One LOWEST thread takes the lock, then one HIGHEST thread asks for it while
+// MEDIUM threads keep the core busy. Whatever the HIGHEST thread waits beyond
+// the length of the critical section is the inversion.
+fn scenario(config: &Config, mitigation: Mitigation, lock: &Lock, run: u64) -> (Outcome, String) {
+    // Inversion needs the threads to compete for one core, so unless told
+    // otherwise they all share the first one.
+    let cores = Some(config.cores.as_deref().unwrap_or(&[0]));
+    let mediums = config.threads.saturating_sub(2).max(1);
+
+    // Every thread has its priority before the holder starts working, so no
+    // thread is still queued at the default priority behind the others.
+    let started = Barrier::new(mediums + 2);
+    let held = OnceLock::new();
+    let published = AtomicU64::new(0);
+    // Bumped once the value is published, so the reader knows it's there.
+    let generation = AtomicU64::new(0);
+    let reader: OnceLock<Thread> = OnceLock::new();
+
+    scope::scope(|scope| {
+        let low = scope.spawn(Priority::Lowest, cores, |_| {
+            let boost = match mitigation {
+                // The immediate ceiling protocol: whoever holds the lock runs
+                // at the highest priority of anyone who may take it.
+                Mitigation::Ceiling => priority::apply(Priority::Highest),
+                _ => String::new(),
+            };
+
+            // Held from the moment the lock is taken, not from when the
+            // thread started asking for it.
+            let critical = || {
+                let since = *held.get_or_init(Instant::now);
+                started.wait();
+                let value = critical_section(config.elements, run);
+                published.store(value, Ordering::Relaxed);
+                (since.elapsed(), value)
+            };
+
+            let (hold, value) = match mitigation {
+                // The update is done on the side and published in one store,
+                // so a reader never has to take a lock for it.
+                Mitigation::LockFree => {
+                    let outcome = critical();
+                    generation.store(run + 1, Ordering::Release);
+                    reader
+                        .get()
+                        .expect("reader known before the barrier")
+                        .unpark();
+                    outcome
+                }
+                _ => lock.with(critical),
+            };
+
+            if mitigation == Mitigation::Ceiling {
+                priority::apply(Priority::Lowest);
+            }
+
+            (hold, value, boost)
+        });
+
+        let high = scope.spawn(Priority::Highest, cores, |_| {
+            _ = reader.set(std::thread::current());
+
+            // Counted from the moment the lock was taken, since a boosted
+            // holder may keep this thread from even asking for it.
+            started.wait();
+            let since = *held.get().expect("lock taken before the barrier");
+
+            let value = match mitigation {
+                // Parked rather than spinning, which would keep the writer
+                // off the core it shares with this thread.
+                Mitigation::LockFree => {
+                    while generation.load(Ordering::Acquire) != run + 1 {
+                        std::thread::park();
+                    }
+                    published.load(Ordering::Relaxed)
+                }
+                _ => lock.with(|| published.load(Ordering::Relaxed)),
+            };
+
+            (since.elapsed(), value)
+        });
+
+        let mediums: Vec<_> = (0..mediums)
+            .map(|i| {
+                let started = &started;
+
+                scope.spawn(Priority::AboveNormal, cores, move |_| {
+                    started.wait();
+
+                    let timer = Instant::now();
+                    critical_section(config.elements * MEDIUM_WORK, i as u64);
+                    timer.elapsed()
+                })
+            })
+            .collect();
+
+        let (hold, written, boost) = joined(low.join());
+        let (wait, read) = joined(high.join());
+        assert_eq!(
+            read, written,
+            "the HIGHEST thread read a value never published"
+        );
+
+        let medium = mediums
+            .into_iter()
+            .map(|medium| joined(medium.join()))
+            .max()
+            .unwrap_or_default();
+
+        (Outcome { wait, hold, medium }, boost)
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub fn run(config: &Config) {
+    // The critical section on its own, with nobody competing for the core.
+    let timer = Instant::now();
+    critical_section(config.elements, 0);
+    let baseline = timer.elapsed();
+
+    let mut table = format!(
+        "{:<10} {:>5} {:>12} {:>12} {:>10} {:>12} {:>14}  {}\n",
+        "mitigation", "n", "wait ms", "max wait ms", "hold ms", "medium ms", "inversion ms", "lock"
+    );
+
+    for &mitigation in &config.mitigations {
+        let (lock, mut description) = Lock::new(mitigation == Mitigation::Inherit);
+        let mut outcomes = vec![];
+
+        for run in 0..config.warmup + config.repeat {
+            let (outcome, boost) = scenario(config, mitigation, &lock, run as u64);
+
+            match mitigation {
+                Mitigation::Ceiling => description = format!("holder boosted to {}", boost),
+                Mitigation::LockFree => description = "atomic handoff, no lock".to_string(),
+                _ => {}
+            }
+
+            if run >= config.warmup {
+                outcomes.push(outcome);
+            }
+        }
+
+        let count = outcomes.len() as f64;
+        let mean = |value: fn(&Outcome) -> Duration| {
+            outcomes
+                .iter()
+                .map(|outcome| millis(value(outcome)))
+                .sum::<f64>()
+                / count
+        };
+
+        let wait = mean(|outcome| outcome.wait);
+        let max_wait = outcomes
+            .iter()
+            .map(|outcome| millis(outcome.wait))
+            .fold(0.0, f64::max);
+
+        // The HIGHEST thread should wait at most one critical section.
+        let inversion = (wait - millis(baseline)).max(0.0);
+
+        _ = writeln!(
+            table,
+            "{:<10} {:>5} {:>12.2} {:>12.2} {:>10.2} {:>12.2} {:>14.2}  {}",
+            mitigation.name(),
+            outcomes.len(),
+            wait,
+            max_wait,
+            mean(|outcome| outcome.hold),
+            mean(|outcome| outcome.medium),
+            inversion,
+            description
+        );
+    }
+
+    print!(
+        "\nPriority inversion, critical section {:.2} ms uncontended\n{}",
+        millis(baseline),
+        table
+    );
+}
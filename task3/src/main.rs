@@ -1,86 +1,361 @@
-use windows::Win32::Foundation::*;
-use windows::Win32::System::Threading::{
-    CreateThread, SetThreadPriority, WaitForMultipleObjects, THREAD_CREATION_FLAGS,
-    THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_HIGHEST,
-    THREAD_PRIORITY_LOWEST,
-};
+mod accounting;
+mod affinity;
+mod config;
+mod inversion;
+mod numa;
+mod priority;
+mod progress;
+mod schedule;
+mod scope;
+mod sort;
+mod stats;
+mod timeline;
+mod workload;
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use accounting::Usage;
+use config::{Config, Format, Scenario};
+use priority::{Priority, Target};
+use progress::Progress;
+use schedule::{Change, Worker};
+use scope::Granted;
+use sort::{Algorithm, Sorter};
+use stats::Sample;
+use workload::Kind;
 
 fn main() {
-    unsafe { spawn_threads(8) }
+    let config = Config::parse(std::env::args().skip(1));
+
+    if config.scenario == Scenario::Inversion {
+        inversion::run(&config);
+        return;
+    }
+
+    // Only sorts have algorithms to compare; everything else runs once.
+    let algorithms = if config.workload.is_sort() {
+        config.algorithms.clone()
+    } else {
+        vec![Algorithm::Unstable]
+    };
+
+    let mut comparison = vec![];
+    let mut failed = 0;
+
+    for &algorithm in &algorithms {
+        let label = if config.workload.is_sort() {
+            format!("{}/{}", config.workload.name(), algorithm.name())
+        } else {
+            config.workload.name().to_string()
+        };
+
+        let (samples, failures) = measure(&config, algorithm);
+        let summaries = stats::summarize(&samples);
+
+        print!("\nWorkload: {}\n{}", label, stats::table(&summaries));
+
+        // Timings of a run that computed the wrong thing mean nothing.
+        let runs = config.warmup + config.repeat;
+        match failures {
+            0 => println!("Verification: all {} runs passed", runs),
+            _ => println!("Verification: {} of {} runs FAILED", failures, runs),
+        }
+        failed += failures;
+
+        // Every algorithm gets its own files when there is more than one.
+        let path = |path: &str| match algorithms.len() {
+            1 => path.to_string(),
+            _ => suffixed(path, algorithm.name()),
+        };
+
+        if let Some(output) = &config.output {
+            let contents = match config.format {
+                Some(Format::Csv) => stats::csv(&summaries),
+                _ => stats::json(&label, &summaries, &samples),
+            };
+
+            std::fs::write(path(output), contents).expect("can't write statistics");
+        }
+
+        if let Some(curves) = &config.curves {
+            std::fs::write(path(curves), stats::curves(&samples)).expect("can't write curves");
+        }
+
+        if let Some(trace) = &config.trace {
+            std::fs::write(path(trace), timeline::chrome_trace(&label, &samples))
+                .expect("can't write trace");
+        }
+
+        comparison.push((label, samples));
+    }
+
+    if comparison.len() > 1 {
+        print!("\n{}", stats::comparison(&comparison));
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// "out.json" becomes "out-radix.json".
+fn suffixed(path: &str, suffix: &str) -> String {
+    let path = std::path::Path::new(path);
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// Also returns how many runs, warm-up ones included, produced a wrong result.
+fn measure(config: &Config, algorithm: Algorithm) -> (Vec<Sample>, usize) {
+    let epoch = Instant::now();
+    let mut samples = vec![];
+    let mut failed = 0;
+
+    for run in 0..config.warmup + config.repeat {
+        let items = spawn_threads(config, epoch, algorithm);
+
+        let failures = items.iter().filter(|item| item.verdict.is_err()).count();
+        let status = match failures {
+            0 => "done".to_string(),
+            _ => format!("FAILED, {} threads produced a wrong result", failures),
+        };
+
+        if failures > 0 {
+            failed += 1;
+        }
+
+        if run < config.warmup {
+            println!("Warm-up run {}/{} {}", run + 1, config.warmup, status);
+            continue;
+        }
+
+        println!(
+            "Run {}/{} {}",
+            run - config.warmup + 1,
+            config.repeat,
+            status
+        );
+
+        for item in items {
+            samples.push(Sample {
+                run: run - config.warmup,
+                thread_num: item.thread_num,
+                priority: item.thread_priority,
+                applied: item.applied,
+                pinned: item.pinned,
+                started: item.started,
+                elapsed: item.elapsed,
+                usage: item.usage,
+                checkpoints: item.progress.checkpoints,
+                changes: item.changes,
+                verdict: item.verdict,
+                setup: item.setup,
+                memory: item.memory,
+                bandwidth: item.bandwidth,
+            });
+        }
+    }
+
+    (samples, failed)
 }
 
 struct ThreadData {
     thread_num: usize,
-    thread_priority: usize,
-    items: Vec<u32>,
+    thread_priority: Priority,
+    applied: String,
+    pinned: String,
+    started: Duration,
+    elapsed: Duration,
+    usage: Usage,
+    progress: Progress,
+    changes: Vec<Change>,
+    verdict: Result<String, String>,
+    workload: Kind,
+    elements: usize,
+    sorter: Sorter,
+    node: Option<usize>,
+    setup: Duration,
+    memory: String,
+    bandwidth: Option<f64>,
 }
 
-unsafe fn spawn_threads(thread_count: usize) {
+fn prepare(config: &Config, epoch: Instant, algorithm: Algorithm) -> Vec<ThreadData> {
     let mut items = vec![];
 
-    for i in 0..thread_count {
+    // Progress curves are only worth their overhead when someone looks at them.
+    let recording =
+        config.trace.is_some() || config.curves.is_some() || !config.schedule.is_empty();
+
+    for i in 0..config.threads {
+        let priority = config.pattern.priority_for(i);
+
+        let sorter = Sorter {
+            algorithm,
+            parts: config.parts,
+            priority,
+            cores: config.cores.clone(),
+        };
+
         items.push(ThreadData {
             thread_num: i,
-            thread_priority: i % 4,
-            items: (0..10_000_000).rev().collect(),
+            thread_priority: priority,
+            applied: String::new(),
+            pinned: String::new(),
+            started: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            usage: Usage::default(),
+            progress: Progress::new(epoch, recording),
+            changes: vec![],
+            verdict: Err("never ran".to_string()),
+            workload: config.workload,
+            elements: config.elements,
+            sorter,
+            node: config.numa.map(|binding| binding.node_for(i)),
+            setup: Duration::ZERO,
+            memory: String::new(),
+            bandwidth: None,
         });
     }
 
-    let mut handles = vec![];
+    items
+}
+
+fn spawn_threads(config: &Config, epoch: Instant, algorithm: Algorithm) -> Vec<ThreadData> {
+    let items = prepare(config, epoch, algorithm);
+
+    let workers: Vec<_> = items
+        .iter()
+        .map(|data| Worker {
+            target: OnceLock::new(),
+            done: data.progress.shared(),
+            base: data.thread_priority,
+        })
+        .collect();
 
-    for thread_data in items.iter_mut() {
-        let priority = match thread_data.thread_priority {
-            0 => THREAD_PRIORITY_LOWEST,
-            1 => THREAD_PRIORITY_BELOW_NORMAL,
-            2 => THREAD_PRIORITY_ABOVE_NORMAL,
-            3 => THREAD_PRIORITY_HIGHEST,
-            _ => unreachable!(),
+    let start = Instant::now();
+
+    let (mut items, changes) = scope::scope(|scope| {
+        let handles: Vec<_> = items
+            .into_iter()
+            .zip(&workers)
+            .map(|(mut data, worker)| {
+                scope.spawn(
+                    data.thread_priority,
+                    config.cores.as_deref(),
+                    move |granted| {
+                        _ = worker.target.set(Target::current());
+                        work(&mut data, granted);
+                        data
+                    },
+                )
+            })
+            .collect();
+
+        let changes = if config.schedule.is_empty() {
+            vec![]
+        } else {
+            schedule::control(
+                &config.schedule,
+                &config.pattern,
+                &workers,
+                start,
+                epoch,
+                |thread| handles[thread].is_finished(),
+            )
         };
 
-        let handle = CreateThread(
-            None,
-            0,
-            Some(work),
-            Some(thread_data as *mut _ as _),
-            THREAD_CREATION_FLAGS(0),
-            None,
-        )
-        .expect("failed to create thread");
+        let items: Vec<_> = handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect();
 
-        SetThreadPriority(handle, priority);
+        (items, changes)
+    });
 
-        handles.push(handle);
+    for change in changes {
+        items[change.thread].changes.push(change);
     }
 
-    WaitForMultipleObjects(&handles, true, u32::MAX);
+    items
+}
 
-    for handle in handles {
-        CloseHandle(handle);
-    }
+fn work(data: &mut ThreadData, granted: &Granted) {
+    data.applied = granted.priority.clone();
+    data.pinned = granted.affinity.clone();
 
-    for item in items {
-        dbg!(item.items.len());
+    // Allocated and first touched here rather than on the main thread, so the
+    // pages end up on the node of the core that works on them.
+    let setup = Instant::now();
+    let binding = data.node.map(numa::bind);
+
+    let mut workload =
+        data.workload
+            .create(data.elements, data.thread_num as u64 + 1, &data.sorter);
+
+    if binding.is_some() {
+        numa::unbind();
     }
-}
 
-unsafe extern "system" fn work(data: *mut std::ffi::c_void) -> u32 {
-    let data = &mut *(data as *mut ThreadData);
+    data.setup = setup.elapsed();
+    data.memory = format!(
+        "{}, {}",
+        binding.as_deref().unwrap_or("first touch"),
+        numa::placement(workload.memory())
+    );
 
-    let timer = std::time::Instant::now();
+    let timer = Instant::now();
+    let start = accounting::snapshot();
+
+    data.started = data.progress.offset(timer);
+    data.progress.mark();
 
     println!(
-        "Thread {} with priority {} started",
-        data.thread_num, data.thread_priority
+        "Thread {} with priority {} ({}) started on {} after {:?} of setup, memory {}",
+        data.thread_num,
+        data.thread_priority.name(),
+        data.applied,
+        data.pinned,
+        data.setup,
+        data.memory
     );
 
-    data.items.sort_unstable();
+    workload.run(&mut data.progress);
+
+    data.elapsed = timer.elapsed();
+    data.progress.mark();
+    data.usage = accounting::snapshot().since(&start);
+
+    data.verdict = workload.verify();
+
+    let bytes = workload.bytes();
+    if bytes > 0 {
+        data.bandwidth = Some(bytes as f64 / 1e9 / data.elapsed.as_secs_f64());
+    }
 
     println!(
-        "Thread {} with priority {} finished in {:?}",
+        "Thread {} with priority {} ({}) finished in {:?}, {}{}",
         data.thread_num,
-        data.thread_priority,
-        timer.elapsed()
+        data.thread_priority.name(),
+        data.applied,
+        data.elapsed,
+        match &data.verdict {
+            Ok(result) => result.clone(),
+            Err(error) => format!("VERIFICATION FAILED: {}", error),
+        },
+        match data.bandwidth {
+            Some(bandwidth) => format!(", {:.2} GB/s from memory {}", bandwidth, data.memory),
+            None => String::new(),
+        }
     );
-
-    0
 }
@@ -0,0 +1,176 @@
+// Where a thread's memory lives. Without a binding every page lands on the
+// node of whichever thread touches it first, which is the worker itself
+// since workloads are built on the thread that runs them.
+#[derive(Clone, Copy)]
+pub enum Binding {
+    Node(usize),
+    Spread,
+}
+
+impl Binding {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "spread" => Self::Spread,
+            node => Self::Node(
+                node.parse()
+                    .unwrap_or_else(|_| panic!("bad NUMA node: {}", node)),
+            ),
+        }
+    }
+
+    pub fn node_for(self, thread_num: usize) -> usize {
+        match self {
+            Self::Node(node) => node,
+            Self::Spread => {
+                let nodes = nodes();
+                nodes[thread_num % nodes.len()]
+            }
+        }
+    }
+}
+
+// How many pages placement looks at, spread evenly over the memory.
+const PLACEMENT_PAGES: usize = 64;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    pub const MPOL_DEFAULT: libc::c_int = 0;
+    pub const MPOL_BIND: libc::c_int = 2;
+
+    // A single word of node mask is plenty for anything this runs on. The
+    // kernel drops the last of the `maxnode` bits, hence the + 1.
+    pub fn set_mempolicy(mode: libc::c_int, mask: libc::c_ulong) -> bool {
+        unsafe {
+            libc::syscall(
+                libc::SYS_set_mempolicy,
+                mode,
+                &mask as *const libc::c_ulong,
+                libc::c_ulong::BITS as libc::c_ulong + 1,
+            ) == 0
+        }
+    }
+}
+
+// The IDs of the nodes that exist, in order. They needn't be 0..n: nodes can
+// be offline or missing from the firmware tables.
+#[cfg(target_os = "linux")]
+pub fn nodes() -> Vec<usize> {
+    let mut nodes: Vec<usize> = std::fs::read_dir("/sys/devices/system/node")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .strip_prefix("node")?
+                        .parse()
+                        .ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if nodes.is_empty() {
+        nodes.push(0);
+    }
+
+    nodes.sort_unstable();
+    nodes
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn nodes() -> Vec<usize> {
+    vec![0]
+}
+
+// Makes the calling thread allocate only from `node` until `unbind`.
+#[cfg(target_os = "linux")]
+pub fn bind(node: usize) -> String {
+    if node < libc::c_ulong::BITS as usize && linux::set_mempolicy(linux::MPOL_BIND, 1 << node) {
+        format!("bound to node {}", node)
+    } else {
+        format!(
+            "first touch, binding to node {} failed: {}",
+            node,
+            std::io::Error::last_os_error()
+        )
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind(_node: usize) -> String {
+    "first touch, no NUMA binding on this platform".to_string()
+}
+
+#[cfg(target_os = "linux")]
+pub fn unbind() {
+    linux::set_mempolicy(linux::MPOL_DEFAULT, 0);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn unbind() {}
+
+// Asks the kernel which node each sampled page is on, e.g. "node 0: 64/64".
+#[cfg(target_os = "linux")]
+pub fn placement(memory: &[u32]) -> String {
+    if memory.is_empty() {
+        return "no memory".to_string();
+    }
+
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let bytes = std::mem::size_of_val(memory);
+    let count = bytes.div_ceil(page).min(PLACEMENT_PAGES);
+
+    let pages: Vec<*const libc::c_void> = (0..count)
+        .map(|i| unsafe { (memory.as_ptr() as *const u8).add(i * bytes / count) as _ })
+        .collect();
+    let mut status = vec![0 as libc::c_int; count];
+
+    let queried = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0,
+            count as libc::c_ulong,
+            pages.as_ptr(),
+            std::ptr::null::<libc::c_int>(),
+            status.as_mut_ptr(),
+            0,
+        )
+    } == 0;
+
+    if !queried {
+        return format!(
+            "unknown, move_pages failed: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    let mut nodes = std::collections::BTreeMap::new();
+    let mut missing = 0;
+
+    for &node in &status {
+        // A negative status is an errno, e.g. for a page that isn't present.
+        if node >= 0 {
+            *nodes.entry(node).or_insert(0) += 1;
+        } else {
+            missing += 1;
+        }
+    }
+
+    let mut parts: Vec<_> = nodes
+        .iter()
+        .map(|(node, pages)| format!("node {}: {}/{}", node, pages, count))
+        .collect();
+
+    if missing > 0 {
+        parts.push(format!("not present: {}/{}", missing, count));
+    }
+
+    parts.join(", ")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn placement(_memory: &[u32]) -> String {
+    "unknown, no placement query on this platform".to_string()
+}
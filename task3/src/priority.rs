@@ -0,0 +1,246 @@
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Priority {
+    Lowest,
+    BelowNormal,
+    AboveNormal,
+    Highest,
+}
+
+impl Priority {
+    pub const ALL: [Self; 4] = [
+        Self::Lowest,
+        Self::BelowNormal,
+        Self::AboveNormal,
+        Self::Highest,
+    ];
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL[index]
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lowest => "lowest",
+            Self::BelowNormal => "below-normal",
+            Self::AboveNormal => "above-normal",
+            Self::Highest => "highest",
+        }
+    }
+
+    // Accepts both the names and the 0..3 indices the experiment always used.
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.name() == value || priority.index().to_string() == value)
+    }
+}
+
+// A thread whose priority can be changed from any other thread, for as long
+// as it runs.
+#[derive(Clone, Copy)]
+pub struct Target(u32);
+
+impl Target {
+    #[cfg(windows)]
+    pub fn current() -> Self {
+        unsafe { Self(windows::Win32::System::Threading::GetCurrentThreadId()) }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn current() -> Self {
+        Self(unsafe { libc::gettid() } as u32)
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    pub fn current() -> Self {
+        Self(0)
+    }
+}
+
+// Sets the priority of the calling thread and describes what the OS actually
+// accepted, which may be less than what was asked for.
+pub fn apply(priority: Priority) -> String {
+    apply_to(Target::current(), priority)
+}
+
+#[cfg(windows)]
+pub fn apply_to(target: Target, priority: Priority) -> String {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenThread, SetThreadPriority, THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL,
+        THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_LOWEST, THREAD_SET_INFORMATION,
+    };
+
+    let (value, name) = match priority {
+        Priority::Lowest => (THREAD_PRIORITY_LOWEST, "THREAD_PRIORITY_LOWEST"),
+        Priority::BelowNormal => (THREAD_PRIORITY_BELOW_NORMAL, "THREAD_PRIORITY_BELOW_NORMAL"),
+        Priority::AboveNormal => (THREAD_PRIORITY_ABOVE_NORMAL, "THREAD_PRIORITY_ABOVE_NORMAL"),
+        Priority::Highest => (THREAD_PRIORITY_HIGHEST, "THREAD_PRIORITY_HIGHEST"),
+    };
+
+    unsafe {
+        let Ok(thread) = OpenThread(THREAD_SET_INFORMATION, false, target.0) else {
+            return "unchanged, OpenThread failed".to_string();
+        };
+
+        let granted = SetThreadPriority(thread, value).as_bool();
+        CloseHandle(thread);
+
+        if granted {
+            name.to_string()
+        } else {
+            "THREAD_PRIORITY_NORMAL, SetThreadPriority failed".to_string()
+        }
+    }
+}
+
+// For a thread that has to preempt every worker, even one at Highest. The
+// thread goes back to what it ran with before once this is dropped.
+pub struct AboveAll {
+    #[cfg(windows)]
+    previous: i32,
+    #[cfg(target_os = "linux")]
+    previous: (libc::c_int, libc::sched_param, libc::c_int),
+    // What the thread runs with instead, when it couldn't be raised.
+    pub refused: Option<String>,
+}
+
+#[cfg(windows)]
+pub fn apply_above_all() -> AboveAll {
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadPriority, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+
+    unsafe {
+        let previous = GetThreadPriority(GetCurrentThread());
+
+        let refused = (!SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_TIME_CRITICAL)
+            .as_bool())
+        .then(|| "unchanged, SetThreadPriority failed".to_string());
+
+        AboveAll { previous, refused }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn apply_above_all() -> AboveAll {
+    let tid = Target::current().0 as libc::pid_t;
+
+    let previous = state(tid);
+    let param = libc::sched_param { sched_priority: 3 };
+
+    let refused = (unsafe { libc::sched_setscheduler(tid, libc::SCHED_FIFO, &param) } != 0)
+        .then(|| apply(Priority::Highest));
+
+    AboveAll { previous, refused }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn apply_above_all() -> AboveAll {
+    AboveAll {
+        refused: Some(apply(Priority::Highest)),
+    }
+}
+
+impl Drop for AboveAll {
+    #[cfg(windows)]
+    fn drop(&mut self) {
+        use windows::Win32::System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_PRIORITY,
+        };
+
+        unsafe {
+            SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY(self.previous));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn drop(&mut self) {
+        let tid = Target::current().0 as libc::pid_t;
+        let (policy, param, nice) = self.previous;
+
+        unsafe {
+            libc::sched_setscheduler(tid, policy, &param);
+            libc::setpriority(libc::PRIO_PROCESS, tid as _, nice);
+        }
+    }
+
+    // Nothing was changed to begin with.
+    #[cfg(not(any(windows, target_os = "linux")))]
+    fn drop(&mut self) {}
+}
+
+#[cfg(target_os = "linux")]
+pub fn apply_to(target: Target, priority: Priority) -> String {
+    let tid = target.0 as libc::pid_t;
+
+    // Addressed by tid, both the policy and the nice value are per thread.
+    let set_scheduler = |policy, priority| unsafe {
+        let param = libc::sched_param {
+            sched_priority: priority,
+        };
+        libc::sched_setscheduler(tid, policy, &param) == 0
+    };
+
+    let set_nice = |nice| unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as _, nice) == 0 };
+
+    // Falling back has to leave whatever policy an earlier change set.
+    let set_other = |nice| set_scheduler(libc::SCHED_OTHER, 0) && set_nice(nice);
+
+    // Real-time policies and negative nice values need CAP_SYS_NICE (or a
+    // matching RLIMIT_RTPRIO/RLIMIT_NICE), so every change falls back step by
+    // step. A step that fails half way may still have changed something.
+    let granted = match priority {
+        Priority::Lowest => set_scheduler(libc::SCHED_IDLE, 0) || set_other(19),
+        Priority::BelowNormal => {
+            (set_scheduler(libc::SCHED_BATCH, 0) && set_nice(10)) || set_other(10)
+        }
+        Priority::AboveNormal => set_scheduler(libc::SCHED_RR, 1) || set_other(-5),
+        Priority::Highest => set_scheduler(libc::SCHED_FIFO, 2) || set_other(-10),
+    };
+
+    if granted {
+        describe(tid)
+    } else {
+        format!("{}, {} refused", describe(tid), priority.name())
+    }
+}
+
+// The policy, its parameters and the nice value of a thread.
+#[cfg(target_os = "linux")]
+fn state(tid: libc::pid_t) -> (libc::c_int, libc::sched_param, libc::c_int) {
+    unsafe {
+        let mut param = libc::sched_param { sched_priority: 0 };
+        libc::sched_getparam(tid, &mut param);
+        (
+            libc::sched_getscheduler(tid),
+            param,
+            libc::getpriority(libc::PRIO_PROCESS, tid as _),
+        )
+    }
+}
+
+// What the thread runs with now, read back from the kernel.
+#[cfg(target_os = "linux")]
+fn describe(tid: libc::pid_t) -> String {
+    let (policy, param, nice) = state(tid);
+
+    match policy {
+        libc::SCHED_FIFO => format!("SCHED_FIFO {}", param.sched_priority),
+        libc::SCHED_RR => format!("SCHED_RR {}", param.sched_priority),
+        libc::SCHED_IDLE => "SCHED_IDLE".to_string(),
+        libc::SCHED_BATCH => format!("SCHED_BATCH nice {}", nice),
+        libc::SCHED_OTHER => format!("SCHED_OTHER nice {}", nice),
+        -1 => "unknown, sched_getscheduler failed".to_string(),
+        other => format!("policy {} nice {}", other, nice),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn apply_to(_target: Target, _priority: Priority) -> String {
+    "default, no priority support on this platform".to_string()
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::accounting;
+
+pub const PERIOD: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy)]
+pub struct Checkpoint {
+    pub at: Duration,
+    pub done: u64,
+    pub cpu: Option<u32>,
+}
+
+// Collects how far a workload got over time. Workloads report the units of
+// work they have done as often as they like; a checkpoint is kept at most
+// once per PERIOD so reporting stays cheap.
+pub struct Progress {
+    epoch: Instant,
+    enabled: bool,
+    last: Instant,
+    done: u64,
+    // The same count, readable by whoever steers the thread from outside.
+    shared: Arc<AtomicU64>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Progress {
+    pub fn new(epoch: Instant, enabled: bool) -> Self {
+        Self {
+            epoch,
+            enabled,
+            last: epoch,
+            done: 0,
+            shared: Arc::default(),
+            checkpoints: vec![],
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn shared(&self) -> Arc<AtomicU64> {
+        self.shared.clone()
+    }
+
+    pub fn offset(&self, at: Instant) -> Duration {
+        at.saturating_duration_since(self.epoch)
+    }
+
+    pub fn tick(&mut self, done: u64) {
+        if !self.enabled {
+            return;
+        }
+
+        self.done = done;
+        self.shared.store(done, Ordering::Relaxed);
+
+        let now = Instant::now();
+
+        if now - self.last >= PERIOD {
+            self.last = now;
+            self.record(now);
+        }
+    }
+
+    // Always recorded, for the very first and last point of a run.
+    pub fn mark(&mut self) {
+        if self.enabled {
+            self.record(Instant::now());
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.checkpoints.push(Checkpoint {
+            at: self.offset(now),
+            done: self.done,
+            cpu: accounting::current_cpu(),
+        });
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::Pattern;
+use crate::priority::{self, Priority, Target};
+
+const TICK: Duration = Duration::from_millis(1);
+
+// Roughly what Windows' balance set manager grants a starved thread: the top
+// priority for a few quanta, then back to where it was.
+const BOOST_TIME: Duration = Duration::from_millis(30);
+
+// Priority changes made while a run is going on, next to the priorities the
+// threads were started with.
+#[derive(Default)]
+pub struct Schedule {
+    pub changes: Vec<(Duration, usize, Priority)>,
+    pub rotate: Option<Duration>,
+    pub boost: Option<Duration>,
+}
+
+impl Schedule {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.rotate.is_none() && self.boost.is_none()
+    }
+
+    // MS:THREAD:PRIORITY, e.g. 500:3:lowest.
+    pub fn parse_change(value: &str) -> (Duration, usize, Priority) {
+        let mut fields = value.splitn(3, ':');
+
+        match (
+            fields.next().and_then(|at| at.parse().ok()),
+            fields.next().and_then(|thread| thread.parse().ok()),
+            fields.next().and_then(Priority::parse),
+        ) {
+            (Some(at), Some(thread), Some(priority)) => {
+                (Duration::from_millis(at), thread, priority)
+            }
+            _ => panic!("bad priority change: {}", value),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Change {
+    pub at: Duration,
+    pub thread: usize,
+    pub priority: Priority,
+    pub granted: String,
+    pub reason: &'static str,
+}
+
+// What the controller knows about one worker, starting with the priority it
+// was started with.
+pub struct Worker {
+    pub target: OnceLock<Target>,
+    pub done: Arc<AtomicU64>,
+    pub base: Priority,
+}
+
+struct Boost {
+    last_done: u64,
+    checked: Duration,
+    until: Option<Duration>,
+}
+
+// Steers the workers from the calling thread until all of them are finished.
+// Times in the schedule count from `start`; the changes it returns count from
+// `epoch` like every other timestamp.
+pub fn control(
+    schedule: &Schedule,
+    pattern: &Pattern,
+    workers: &[Worker],
+    start: Instant,
+    epoch: Instant,
+    finished: impl Fn(usize) -> bool,
+) -> Vec<Change> {
+    // The controller must not starve behind the threads it is steering, and
+    // goes back to its old priority once they are done.
+    let above_all = priority::apply_above_all();
+
+    if let Some(state) = &above_all.refused {
+        println!(
+            "Controller couldn't be raised above the workers and runs at {}",
+            state
+        );
+    }
+
+    let mut changes = vec![];
+    let mut scripted = schedule.changes.clone();
+    let mut rotation = 0;
+    let mut bases: Vec<_> = workers.iter().map(|worker| worker.base).collect();
+    let mut boosts: Vec<_> = workers
+        .iter()
+        .map(|_| Boost {
+            last_done: 0,
+            checked: Duration::ZERO,
+            until: None,
+        })
+        .collect();
+
+    let mut apply = |worker: &Worker, thread, priority, reason| {
+        // A thread that hasn't started yet gets its priority when it does.
+        let Some(&target) = worker.target.get() else {
+            return;
+        };
+
+        changes.push(Change {
+            at: Instant::now().saturating_duration_since(epoch),
+            thread,
+            priority,
+            granted: priority::apply_to(target, priority),
+            reason,
+        });
+    };
+
+    while !(0..workers.len()).all(&finished) {
+        std::thread::sleep(TICK);
+
+        let now = start.elapsed();
+
+        scripted.retain(|&(at, thread, priority)| {
+            if at > now {
+                return true;
+            }
+            if let Some(worker) = workers.get(thread) {
+                bases[thread] = priority;
+                if !finished(thread) {
+                    apply(worker, thread, priority, "scripted");
+                }
+            }
+            false
+        });
+
+        // Every period each thread moves on to the priority of its neighbour.
+        if let Some(period) = schedule.rotate {
+            let step = (now.as_millis() / period.as_millis().max(1)) as usize;
+
+            if step > rotation {
+                rotation = step;
+
+                for (thread, worker) in workers.iter().enumerate() {
+                    bases[thread] = pattern.priority_for(thread + step);
+                    if !finished(thread) {
+                        apply(worker, thread, bases[thread], "rotate");
+                    }
+                }
+            }
+        }
+
+        // A thread that made no progress for a whole period is starving.
+        if let Some(period) = schedule.boost {
+            for (thread, (worker, boost)) in workers.iter().zip(&mut boosts).enumerate() {
+                if finished(thread) || worker.target.get().is_none() {
+                    continue;
+                }
+
+                if let Some(until) = boost.until {
+                    if now >= until {
+                        boost.until = None;
+                        apply(worker, thread, bases[thread], "restore");
+                    }
+                    continue;
+                }
+
+                if now - boost.checked < period {
+                    continue;
+                }
+
+                let done = worker.done.load(Ordering::Relaxed);
+
+                if done == boost.last_done && bases[thread] != Priority::Highest {
+                    boost.until = Some(now + BOOST_TIME);
+                    apply(worker, thread, Priority::Highest, "boost");
+                }
+
+                boost.last_done = done;
+                boost.checked = now;
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_change() {
+        assert_eq!(
+            Schedule::parse_change("500:3:lowest"),
+            (Duration::from_millis(500), 3, Priority::Lowest)
+        );
+        assert_eq!(
+            Schedule::parse_change("0:0:3"),
+            (Duration::ZERO, 0, Priority::Highest)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "bad priority change")]
+    fn parse_change_rejects_unknown_priority() {
+        Schedule::parse_change("500:3:realtime");
+    }
+
+    #[test]
+    #[should_panic(expected = "bad priority change")]
+    fn parse_change_rejects_missing_fields() {
+        Schedule::parse_change("500:3");
+    }
+}
@@ -0,0 +1,70 @@
+use std::thread::{Result, Scope, ScopedJoinHandle};
+
+use crate::affinity;
+use crate::priority::{self, Priority};
+
+// What the OS accepted for a thread, which may be less than what was asked for.
+pub struct Granted {
+    pub priority: String,
+    pub affinity: String,
+}
+
+// Scoped threads that start with a priority and, optionally, a core set.
+// Borrowing works exactly like `std::thread::scope`: every thread is joined
+// before `scope` returns, so closures may hold references into the caller.
+pub struct PriorityScope<'scope, 'env> {
+    scope: &'scope Scope<'scope, 'env>,
+}
+
+pub struct PriorityHandle<'scope, T> {
+    inner: ScopedJoinHandle<'scope, T>,
+}
+
+impl<T> PriorityHandle<'_, T> {
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    // A panic in the thread comes back as `Err` with the panic payload.
+    pub fn join(self) -> Result<T> {
+        self.inner.join()
+    }
+}
+
+impl<'scope> PriorityScope<'scope, '_> {
+    pub fn spawn<F, T>(
+        &self,
+        priority: Priority,
+        cores: Option<&'scope [usize]>,
+        work: F,
+    ) -> PriorityHandle<'scope, T>
+    where
+        F: FnOnce(&Granted) -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let inner = self.scope.spawn(move || {
+            // Both are set from inside the thread so every platform reports
+            // what it actually granted to this thread.
+            let granted = Granted {
+                affinity: match cores {
+                    Some(cores) => affinity::apply(cores),
+                    None => "any core".to_string(),
+                },
+                priority: priority::apply(priority),
+            };
+
+            work(&granted)
+        });
+
+        PriorityHandle { inner }
+    }
+}
+
+// Threads that were never joined and panicked make `scope` panic too, just
+// like `std::thread::scope`.
+pub fn scope<'env, F, T>(run: F) -> T
+where
+    F: for<'scope> FnOnce(&PriorityScope<'scope, 'env>) -> T,
+{
+    std::thread::scope(|scope| run(&PriorityScope { scope }))
+}
@@ -0,0 +1,290 @@
+use crate::priority::Priority;
+use crate::progress::Progress;
+use crate::scope;
+use crate::workload::REPORT_EVERY;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    Unstable,
+    Stable,
+    Radix,
+    Heap,
+    Merge,
+}
+
+impl Algorithm {
+    pub const ALL: [Self; 5] = [
+        Self::Unstable,
+        Self::Stable,
+        Self::Radix,
+        Self::Heap,
+        Self::Merge,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unstable => "unstable",
+            Self::Stable => "stable",
+            Self::Radix => "radix",
+            Self::Heap => "heap",
+            Self::Merge => "merge",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == value)
+    }
+}
+
+// How a sort workload sorts. The parallel merge sort hands its parts to
+// helper threads running at the same priority and on the same cores as the
+// thread that owns the array.
+#[derive(Clone)]
+pub struct Sorter {
+    pub algorithm: Algorithm,
+    pub parts: usize,
+    pub priority: Priority,
+    pub cores: Option<Vec<usize>>,
+}
+
+impl Default for Sorter {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Unstable,
+            parts: 1,
+            priority: Priority::Lowest,
+            cores: None,
+        }
+    }
+}
+
+impl Sorter {
+    pub fn sort(&self, items: &mut [u32], progress: &mut Progress) {
+        match self.algorithm {
+            Algorithm::Unstable if !progress.enabled() => items.sort_unstable(),
+            Algorithm::Stable if !progress.enabled() => items.sort(),
+            // A sort can't be paused, so progress is counted in comparisons.
+            Algorithm::Unstable => items.sort_unstable_by(counting(progress)),
+            Algorithm::Stable => items.sort_by(counting(progress)),
+            Algorithm::Radix => radix(items, progress),
+            Algorithm::Heap => heap(items, progress),
+            Algorithm::Merge => self.merge(items, progress),
+        }
+    }
+
+    fn merge(&self, items: &mut [u32], progress: &mut Progress) {
+        let part = items.len().div_ceil(self.parts.max(1)).max(1);
+
+        scope::scope(|scope| {
+            let handles: Vec<_> = items
+                .chunks_mut(part)
+                .map(|chunk| {
+                    scope.spawn(self.priority, self.cores.as_deref(), move |_| {
+                        chunk.sort_unstable()
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            }
+        });
+
+        progress.tick(items.len() as u64);
+
+        // Bottom-up: merge neighbouring runs into the buffer until a single
+        // run is left. The pairs of a pass are merged side by side by helpers
+        // like the ones that sorted the parts.
+        let mut buffer = vec![0; items.len()];
+        let mut width = part;
+        let mut done = items.len() as u64;
+
+        while width < items.len() {
+            scope::scope(|scope| {
+                let handles: Vec<_> = items
+                    .chunks(width * 2)
+                    .zip(buffer.chunks_mut(width * 2))
+                    .map(|(from, to)| {
+                        scope.spawn(self.priority, self.cores.as_deref(), move |_| {
+                            let (left, right) = from.split_at(width.min(from.len()));
+                            merge_into(left, right, to);
+                        })
+                    })
+                    .collect();
+
+                for handle in handles {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                }
+            });
+
+            items.copy_from_slice(&buffer);
+            width *= 2;
+
+            done += items.len() as u64;
+            progress.tick(done);
+        }
+    }
+}
+
+fn counting(progress: &mut Progress) -> impl FnMut(&u32, &u32) -> std::cmp::Ordering + '_ {
+    let mut comparisons = 0u64;
+
+    move |a, b| {
+        comparisons += 1;
+        if comparisons.is_multiple_of(REPORT_EVERY) {
+            progress.tick(comparisons);
+        }
+        a.cmp(b)
+    }
+}
+
+fn merge_into(left: &[u32], right: &[u32], to: &mut [u32]) {
+    let (mut i, mut j) = (0, 0);
+
+    for slot in to.iter_mut() {
+        if j == right.len() || (i < left.len() && left[i] <= right[j]) {
+            *slot = left[i];
+            i += 1;
+        } else {
+            *slot = right[j];
+            j += 1;
+        }
+    }
+}
+
+// LSD radix sort, one byte per pass, scattering into a buffer and back.
+fn radix(items: &mut [u32], progress: &mut Progress) {
+    let mut buffer = vec![0; items.len()];
+
+    for pass in 0..4 {
+        let shift = pass * 8;
+        let digit = |value: u32| (value >> shift) as usize & 0xff;
+
+        let mut offsets = [0usize; 256];
+        for &value in items.iter() {
+            offsets[digit(value)] += 1;
+        }
+
+        let mut total = 0;
+        for offset in offsets.iter_mut() {
+            let count = *offset;
+            *offset = total;
+            total += count;
+        }
+
+        for &value in items.iter() {
+            let slot = &mut offsets[digit(value)];
+            buffer[*slot] = value;
+            *slot += 1;
+        }
+
+        items.copy_from_slice(&buffer);
+        progress.tick((pass as u64 + 1) * items.len() as u64);
+    }
+}
+
+fn heap(items: &mut [u32], progress: &mut Progress) {
+    let mut moves = 0u64;
+
+    let mut sift_down = |items: &mut [u32], mut root: usize, end: usize| loop {
+        let mut child = root * 2 + 1;
+
+        if child >= end {
+            break;
+        }
+        if child + 1 < end && items[child] < items[child + 1] {
+            child += 1;
+        }
+        if items[root] >= items[child] {
+            break;
+        }
+
+        items.swap(root, child);
+        root = child;
+
+        moves += 1;
+        if moves.is_multiple_of(REPORT_EVERY) {
+            progress.tick(moves);
+        }
+    };
+
+    for root in (0..items.len() / 2).rev() {
+        sift_down(items, root, items.len());
+    }
+
+    for end in (1..items.len()).rev() {
+        items.swap(0, end);
+        sift_down(items, 0, end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    // Sizes around the edges of every algorithm: nothing, one item, odd
+    // lengths that don't split evenly into parts, plus runs of duplicates.
+    fn inputs() -> Vec<Vec<u32>> {
+        let mut state = 0x9e37_79b9u32;
+        let mut random = |length: usize| -> Vec<u32> {
+            (0..length)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state
+                })
+                .collect()
+        };
+
+        vec![
+            vec![],
+            vec![7],
+            vec![2, 1],
+            random(3),
+            random(1001),
+            (0..500).rev().collect(),
+            (0..777).map(|i| i % 5).collect(),
+            vec![u32::MAX, 0, u32::MAX, 1 << 24, 1 << 16, 1 << 8],
+        ]
+    }
+
+    fn check(sorter: &Sorter) {
+        for input in inputs() {
+            let mut expected = input.clone();
+            expected.sort_unstable();
+
+            let mut items = input;
+            sorter.sort(&mut items, &mut Progress::new(Instant::now(), true));
+
+            assert_eq!(
+                items,
+                expected,
+                "{} with {} parts",
+                sorter.algorithm.name(),
+                sorter.parts
+            );
+        }
+    }
+
+    #[test]
+    fn radix_heap_and_merge_sort() {
+        for algorithm in [Algorithm::Radix, Algorithm::Heap, Algorithm::Merge] {
+            for parts in [1, 2, 3, 4, 7] {
+                check(&Sorter {
+                    algorithm,
+                    parts,
+                    ..Sorter::default()
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::accounting::Usage;
+use crate::priority::Priority;
+use crate::progress::Checkpoint;
+use crate::schedule::Change;
+
+pub struct Sample {
+    pub run: usize,
+    pub thread_num: usize,
+    pub priority: Priority,
+    pub applied: String,
+    pub pinned: String,
+    pub started: Duration,
+    pub elapsed: Duration,
+    pub usage: Usage,
+    pub checkpoints: Vec<Checkpoint>,
+    pub changes: Vec<Change>,
+    pub verdict: Result<String, String>,
+    pub setup: Duration,
+    pub memory: String,
+    // GB/s, for workloads that measure bandwidth.
+    pub bandwidth: Option<f64>,
+}
+
+pub struct Summary {
+    pub priority: Priority,
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub cpu: f64,
+    pub waiting: f64,
+    pub voluntary: Option<f64>,
+    pub involuntary: Option<f64>,
+    pub bandwidth: Option<f64>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Nearest-rank percentile over already sorted values.
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let middle = sorted.len() / 2;
+
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+pub fn summarize(samples: &[Sample]) -> Vec<Summary> {
+    let mut summaries = vec![];
+
+    for priority in Priority::ALL {
+        let class: Vec<_> = samples
+            .iter()
+            .filter(|sample| sample.priority == priority)
+            .collect();
+
+        let mut times: Vec<_> = class.iter().map(|sample| millis(sample.elapsed)).collect();
+
+        if times.is_empty() {
+            continue;
+        }
+
+        times.sort_by(f64::total_cmp);
+
+        let count = times.len();
+        let mean = times.iter().sum::<f64>() / count as f64;
+        let variance = if count > 1 {
+            times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+
+        let average = |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64;
+
+        let switches = |counter: fn(&Usage) -> Option<u64>| {
+            let counts: Option<Vec<_>> = class
+                .iter()
+                .map(|sample| counter(&sample.usage).map(|count| count as f64))
+                .collect();
+            counts.map(average)
+        };
+
+        let bandwidths: Option<Vec<_>> = class.iter().map(|sample| sample.bandwidth).collect();
+
+        summaries.push(Summary {
+            priority,
+            count,
+            mean,
+            median: median(&times),
+            p95: percentile(&times, 95.0),
+            stddev: variance.sqrt(),
+            min: times[0],
+            max: times[count - 1],
+            cpu: average(
+                class
+                    .iter()
+                    .map(|sample| millis(sample.usage.cpu_time()))
+                    .collect(),
+            ),
+            waiting: average(
+                class
+                    .iter()
+                    .map(|sample| millis(sample.usage.waiting(sample.elapsed)))
+                    .collect(),
+            ),
+            voluntary: switches(|usage| usage.voluntary),
+            involuntary: switches(|usage| usage.involuntary),
+            bandwidth: bandwidths.map(average),
+        });
+    }
+
+    summaries
+}
+
+pub fn table(summaries: &[Summary]) -> String {
+    let mut table = format!(
+        "{:<14} {:>5} {:>10} {:>10} {:>10} {:>10}\n",
+        "priority", "n", "mean ms", "median ms", "p95 ms", "stddev ms"
+    );
+
+    for summary in summaries {
+        _ = writeln!(
+            table,
+            "{:<14} {:>5} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            summary.priority.name(),
+            summary.count,
+            summary.mean,
+            summary.median,
+            summary.p95,
+            summary.stddev
+        );
+    }
+
+    _ = writeln!(
+        table,
+        "\n{:<14} {:>10} {:>10} {:>8} {:>10} {:>10}",
+        "priority", "cpu ms", "wait ms", "wait %", "vol cs", "invol cs"
+    );
+
+    let count = |switches: Option<f64>| match switches {
+        Some(switches) => format!("{:.1}", switches),
+        None => "n/a".to_string(),
+    };
+
+    for summary in summaries {
+        _ = writeln!(
+            table,
+            "{:<14} {:>10.2} {:>10.2} {:>8.1} {:>10} {:>10}",
+            summary.priority.name(),
+            summary.cpu,
+            summary.waiting,
+            summary.waiting / summary.mean * 100.0,
+            count(summary.voluntary),
+            count(summary.involuntary)
+        );
+    }
+
+    if summaries.iter().any(|summary| summary.bandwidth.is_some()) {
+        _ = writeln!(table, "\n{:<14} {:>10}", "priority", "GB/s");
+
+        for summary in summaries {
+            _ = writeln!(
+                table,
+                "{:<14} {:>10}",
+                summary.priority.name(),
+                summary
+                    .bandwidth
+                    .map_or("n/a".to_string(), |bandwidth| format!("{:.2}", bandwidth))
+            );
+        }
+    }
+
+    // Summaries go from lowest to highest priority, so every step up should
+    // have a smaller mean.
+    let inversions: Vec<_> = summaries
+        .windows(2)
+        .filter(|pair| pair[1].mean >= pair[0].mean)
+        .map(|pair| format!("{} >= {}", pair[1].priority.name(), pair[0].priority.name()))
+        .collect();
+
+    if summaries.len() < 2 {
+        table.push_str("Only one priority class, nothing to compare\n");
+    } else if inversions.is_empty() {
+        table.push_str("Higher priority finished sooner: yes\n");
+    } else {
+        _ = writeln!(
+            table,
+            "Higher priority finished sooner: no ({})",
+            inversions.join(", ")
+        );
+    }
+
+    table
+}
+
+// One line per workload or algorithm, over every thread regardless of its
+// priority, fastest first.
+pub fn comparison(results: &[(String, Vec<Sample>)]) -> String {
+    let mut rows: Vec<_> = results
+        .iter()
+        .map(|(label, samples)| {
+            let mut times: Vec<_> = samples
+                .iter()
+                .map(|sample| millis(sample.elapsed))
+                .collect();
+            times.sort_by(f64::total_cmp);
+
+            let mean = times.iter().sum::<f64>() / times.len() as f64;
+            (label, mean, median(&times), times[times.len() - 1])
+        })
+        .collect();
+
+    rows.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut table = format!(
+        "{:<28} {:>10} {:>10} {:>10}\n",
+        "algorithm", "mean ms", "median ms", "max ms"
+    );
+
+    for (label, mean, median, max) in rows {
+        _ = writeln!(
+            table,
+            "{:<28} {:>10.2} {:>10.2} {:>10.2}",
+            label, mean, median, max
+        );
+    }
+
+    table
+}
+
+pub fn csv(summaries: &[Summary]) -> String {
+    let mut csv = "priority,count,mean_ms,median_ms,p95_ms,stddev_ms,min_ms,max_ms,cpu_ms,wait_ms,voluntary_cs,involuntary_cs,bandwidth_gbs\n".to_string();
+
+    let count = |switches: Option<f64>| switches.map(|switches| switches.to_string());
+
+    for summary in summaries {
+        _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            summary.priority.name(),
+            summary.count,
+            summary.mean,
+            summary.median,
+            summary.p95,
+            summary.stddev,
+            summary.min,
+            summary.max,
+            summary.cpu,
+            summary.waiting,
+            count(summary.voluntary).unwrap_or_default(),
+            count(summary.involuntary).unwrap_or_default(),
+            count(summary.bandwidth).unwrap_or_default()
+        );
+    }
+
+    csv
+}
+
+// Every checkpoint with the priority the thread had at that moment, so the
+// effect of a change shows up as a bend in the curve.
+pub fn curves(samples: &[Sample]) -> String {
+    let mut csv = "run,thread,ms,done,priority\n".to_string();
+
+    for sample in samples {
+        for checkpoint in &sample.checkpoints {
+            let priority = sample
+                .changes
+                .iter()
+                .rev()
+                .find(|change| change.at <= checkpoint.at)
+                .map_or(sample.priority, |change| change.priority);
+
+            _ = writeln!(
+                csv,
+                "{},{},{},{},{}",
+                sample.run,
+                sample.thread_num,
+                millis(checkpoint.at),
+                checkpoint.done,
+                priority.name()
+            );
+        }
+    }
+
+    csv
+}
+
+// A string as it has to appear between the quotes of a JSON string.
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if (character as u32) < 0x20 => {
+                _ = write!(escaped, "\\u{:04x}", character as u32)
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+fn json_number<T: ToString>(value: Option<T>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+pub fn json(workload: &str, summaries: &[Summary], samples: &[Sample]) -> String {
+    let classes: Vec<_> = summaries
+        .iter()
+        .map(|summary| {
+            format!(
+                "{{\"priority\":\"{}\",\"count\":{},\"mean_ms\":{},\"median_ms\":{},\"p95_ms\":{},\"stddev_ms\":{},\"min_ms\":{},\"max_ms\":{},\"cpu_ms\":{},\"wait_ms\":{},\"voluntary_cs\":{},\"involuntary_cs\":{},\"bandwidth_gbs\":{}}}",
+                summary.priority.name(),
+                summary.count,
+                summary.mean,
+                summary.median,
+                summary.p95,
+                summary.stddev,
+                summary.min,
+                summary.max,
+                summary.cpu,
+                summary.waiting,
+                json_number(summary.voluntary),
+                json_number(summary.involuntary),
+                json_number(summary.bandwidth)
+            )
+        })
+        .collect();
+
+    let samples: Vec<_> = samples
+        .iter()
+        .map(|sample| {
+            format!(
+                "{{\"run\":{},\"thread\":{},\"priority\":\"{}\",\"applied\":\"{}\",\"pinned\":\"{}\",\"verified\":{},\"result\":\"{}\",\"memory\":\"{}\",\"setup_ms\":{},\"elapsed_ms\":{},\"user_ms\":{},\"system_ms\":{},\"wait_ms\":{},\"voluntary_cs\":{},\"involuntary_cs\":{},\"cpu\":{},\"bandwidth_gbs\":{}}}",
+                sample.run,
+                sample.thread_num,
+                sample.priority.name(),
+                json_escape(&sample.applied),
+                json_escape(&sample.pinned),
+                sample.verdict.is_ok(),
+                json_escape(match &sample.verdict {
+                    Ok(result) | Err(result) => result,
+                }),
+                json_escape(&sample.memory),
+                millis(sample.setup),
+                millis(sample.elapsed),
+                millis(sample.usage.user),
+                millis(sample.usage.system),
+                millis(sample.usage.waiting(sample.elapsed)),
+                json_number(sample.usage.voluntary),
+                json_number(sample.usage.involuntary),
+                json_number(sample.usage.cpu),
+                json_number(sample.bandwidth)
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"workload\":\"{}\",\"classes\":[{}],\"samples\":[{}]}}\n",
+        json_escape(workload),
+        classes.join(","),
+        samples.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+
+        assert_eq!(percentile(&values, 95.0), 10.0);
+        assert_eq!(percentile(&values, 50.0), 5.0);
+        assert_eq!(percentile(&values, 10.0), 1.0);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&[3.0], 95.0), 3.0);
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[1.0]), 1.0);
+        assert_eq!(median(&[1.0, 2.0, 10.0]), 2.0);
+        assert_eq!(median(&[1.0, 2.0, 4.0, 10.0]), 3.0);
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(
+            json_escape("say \"hi\"\\\n\t"),
+            "say \\\"hi\\\"\\\\\\n\\u0009"
+        );
+    }
+}
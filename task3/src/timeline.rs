@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+
+use crate::progress;
+use crate::stats::{json_escape, Sample};
+
+fn micros(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+// Checkpoints further apart than this were not taken while the thread ran all
+// along: it was off the core for some of the time in between.
+const CONTINUOUS: std::time::Duration = progress::PERIOD.saturating_mul(2);
+
+// Offset for the per-core view of a run, so it never collides with the pid
+// used for the per-thread view.
+const CORES: usize = 1_000_000;
+
+// Chrome Trace Event format, readable by chrome://tracing and Perfetto. Every
+// run is a process with one track per thread, plus a second process with one
+// track per core showing which thread the core was running at each
+// checkpoint, and for how long where the checkpoints are close enough to
+// tell.
+pub fn chrome_trace(workload: &str, samples: &[Sample]) -> String {
+    let mut events = vec![];
+    let mut cores = BTreeSet::new();
+
+    let runs = samples
+        .iter()
+        .map(|sample| sample.run)
+        .max()
+        .map_or(0, |run| run + 1);
+
+    for run in 0..runs {
+        events.push(format!(
+            "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{},\"args\":{{\"name\":\"run {} threads\"}}}}",
+            run, run
+        ));
+        events.push(format!(
+            "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{},\"args\":{{\"name\":\"run {} cores\"}}}}",
+            CORES + run,
+            run
+        ));
+    }
+
+    let workload = json_escape(workload);
+
+    for sample in samples {
+        let label = format!("thread {} {}", sample.thread_num, sample.priority.name());
+
+        events.push(format!(
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+            sample.run, sample.thread_num, label
+        ));
+
+        events.push(format!(
+            "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":{},\"tid\":{},\"ts\":{},\"dur\":{},\"args\":{{\"applied\":\"{}\"}}}}",
+            workload,
+            sample.priority.name(),
+            sample.run,
+            sample.thread_num,
+            micros(sample.started),
+            micros(sample.elapsed),
+            json_escape(&sample.applied)
+        ));
+
+        for checkpoint in &sample.checkpoints {
+            events.push(format!(
+                "{{\"name\":\"{} progress\",\"ph\":\"C\",\"pid\":{},\"ts\":{},\"args\":{{\"done\":{}}}}}",
+                label,
+                sample.run,
+                micros(checkpoint.at),
+                checkpoint.done
+            ));
+        }
+
+        for change in &sample.changes {
+            events.push(format!(
+                "{{\"name\":\"{} {}\",\"ph\":\"i\",\"s\":\"t\",\"pid\":{},\"tid\":{},\"ts\":{},\"args\":{{\"granted\":\"{}\"}}}}",
+                change.reason,
+                change.priority.name(),
+                sample.run,
+                sample.thread_num,
+                micros(change.at),
+                json_escape(&change.granted)
+            ));
+        }
+
+        for (i, checkpoint) in sample.checkpoints.iter().enumerate() {
+            let Some(cpu) = checkpoint.cpu else {
+                continue;
+            };
+
+            cores.insert((sample.run, cpu));
+
+            // Running from the previous checkpoint on, if it came just before
+            // on the same core; otherwise all that is known is this moment.
+            let previous = i
+                .checked_sub(1)
+                .map(|i| sample.checkpoints[i])
+                .filter(|previous| {
+                    previous.cpu == Some(cpu) && checkpoint.at - previous.at <= CONTINUOUS
+                });
+
+            events.push(match previous {
+                Some(previous) => format!(
+                    "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":{},\"tid\":{},\"ts\":{},\"dur\":{}}}",
+                    label,
+                    sample.priority.name(),
+                    CORES + sample.run,
+                    cpu,
+                    micros(previous.at),
+                    micros(checkpoint.at - previous.at)
+                ),
+                None => format!(
+                    "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"i\",\"s\":\"t\",\"pid\":{},\"tid\":{},\"ts\":{}}}",
+                    label,
+                    sample.priority.name(),
+                    CORES + sample.run,
+                    cpu,
+                    micros(checkpoint.at)
+                ),
+            });
+        }
+    }
+
+    for (run, cpu) in cores {
+        events.push(format!(
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"core {}\"}}}}",
+            CORES + run,
+            cpu,
+            cpu
+        ));
+    }
+
+    format!(
+        "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+        events.join(",\n")
+    )
+}
@@ -0,0 +1,419 @@
+use std::hint::black_box;
+use std::time::Duration;
+
+use crate::progress::Progress;
+use crate::sort::Sorter;
+
+// How many units of work go by between two progress reports.
+pub const REPORT_EVERY: u64 = 4096;
+
+pub trait Workload: Send {
+    fn run(&mut self, progress: &mut Progress);
+
+    // Checks the result of the last run, outside of the timed part. Ok
+    // describes the result, Err what is wrong with it.
+    fn verify(&self) -> Result<String, String>;
+
+    // The bulk of the memory the workload works on, if it has any.
+    fn memory(&self) -> &[u32] {
+        &[]
+    }
+
+    // How many bytes of memory a run streams through, for workloads that
+    // measure bandwidth.
+    fn bytes(&self) -> u64 {
+        0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    SortRandom,
+    SortSorted,
+    SortReverse,
+    SortDuplicates,
+    Alu,
+    PointerChase,
+    Hash,
+    Bandwidth,
+    Sleep,
+}
+
+impl Kind {
+    pub const ALL: [Self; 9] = [
+        Self::SortRandom,
+        Self::SortSorted,
+        Self::SortReverse,
+        Self::SortDuplicates,
+        Self::Alu,
+        Self::PointerChase,
+        Self::Hash,
+        Self::Bandwidth,
+        Self::Sleep,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SortRandom => "sort-random",
+            Self::SortSorted => "sort-sorted",
+            Self::SortReverse => "sort-reverse",
+            Self::SortDuplicates => "sort-duplicates",
+            Self::Alu => "alu",
+            Self::PointerChase => "pointer-chase",
+            Self::Hash => "hash",
+            Self::Bandwidth => "bandwidth",
+            Self::Sleep => "sleep",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == value)
+    }
+
+    pub fn is_sort(self) -> bool {
+        matches!(
+            self,
+            Self::SortRandom | Self::SortSorted | Self::SortReverse | Self::SortDuplicates
+        )
+    }
+
+    // Only the sort kinds look at the sorter.
+    pub fn create(self, elements: usize, seed: u64, sorter: &Sorter) -> Box<dyn Workload> {
+        let mut rng = Rng::new(seed);
+
+        match self {
+            Self::SortRandom => Sort::boxed((0..elements).map(|_| rng.next() as u32), sorter),
+            Self::SortSorted => Sort::boxed(0..elements as u32, sorter),
+            Self::SortReverse => Sort::boxed((0..elements as u32).rev(), sorter),
+            Self::SortDuplicates => {
+                Sort::boxed((0..elements).map(|_| rng.next() as u32 % 16), sorter)
+            }
+            Self::Alu => Box::new(Alu {
+                iterations: elements,
+                seed,
+                value: None,
+            }),
+            Self::PointerChase => Box::new(PointerChase::new(elements, &mut rng)),
+            Self::Hash => Box::new(Hash {
+                data: (0..elements).map(|_| rng.next() as u32).collect(),
+                hash: None,
+            }),
+            Self::Bandwidth => Box::new(Bandwidth {
+                data: (0..elements as u32).collect(),
+                sum: None,
+            }),
+            Self::Sleep => Box::new(Sleep {
+                data: (0..elements as u32).collect(),
+                sum: None,
+            }),
+        }
+    }
+}
+
+// xorshift64*, good enough to shuffle benchmark inputs reproducibly.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+// Order doesn't change it, so any sort must leave it as it was.
+fn checksum(items: &[u32]) -> (u64, u64) {
+    items.iter().fold((0, 0), |(sum, squares), &value| {
+        let value = value as u64;
+        (sum.wrapping_add(value), squares.wrapping_add(value * value))
+    })
+}
+
+struct Sort {
+    items: Vec<u32>,
+    sorter: Sorter,
+    checksum: (u64, u64),
+}
+
+impl Sort {
+    fn boxed(items: impl Iterator<Item = u32>, sorter: &Sorter) -> Box<dyn Workload> {
+        let items: Vec<_> = items.collect();
+
+        Box::new(Self {
+            checksum: checksum(&items),
+            items,
+            sorter: sorter.clone(),
+        })
+    }
+}
+
+impl Workload for Sort {
+    fn run(&mut self, progress: &mut Progress) {
+        self.sorter.sort(&mut self.items, progress);
+    }
+
+    fn memory(&self) -> &[u32] {
+        &self.items
+    }
+
+    fn verify(&self) -> Result<String, String> {
+        let algorithm = self.sorter.algorithm.name();
+
+        if let Some(i) = self.items.windows(2).position(|pair| pair[0] > pair[1]) {
+            return Err(format!(
+                "{} sort left items {} and {} out of order",
+                algorithm,
+                i,
+                i + 1
+            ));
+        }
+
+        let (sum, squares) = checksum(&self.items);
+
+        if (sum, squares) != self.checksum {
+            return Err(format!("{} sort changed the items", algorithm));
+        }
+
+        Ok(format!("sorted, checksum {:016x}{:016x}", sum, squares))
+    }
+}
+
+struct Alu {
+    iterations: usize,
+    seed: u64,
+    value: Option<u64>,
+}
+
+fn alu_step(value: u64, i: u64) -> u64 {
+    let value = value.wrapping_mul(6364136223846793005).wrapping_add(i);
+    value ^ value >> 29
+}
+
+// The same chain of steps as the ALU workload, for code that needs its result
+// rather than a workload to run.
+pub fn alu(iterations: usize, seed: u64) -> u64 {
+    (0..iterations as u64).fold(seed | 1, alu_step)
+}
+
+impl Workload for Alu {
+    fn run(&mut self, progress: &mut Progress) {
+        let mut value = self.seed | 1;
+
+        for i in 0..self.iterations as u64 {
+            value = alu_step(value, i);
+
+            if i.is_multiple_of(REPORT_EVERY) {
+                progress.tick(i);
+            }
+        }
+
+        self.value = Some(value);
+    }
+
+    fn verify(&self) -> Result<String, String> {
+        let expected = alu(self.iterations, self.seed);
+
+        match self.value {
+            Some(value) if value == expected => Ok(format!("value {:016x}", value)),
+            Some(value) => Err(format!("value {:016x}, expected {:016x}", value, expected)),
+            None => Err("never ran".to_string()),
+        }
+    }
+}
+
+// Walks a single random cycle through the whole array, so every load misses
+// the cache once the array is bigger than it.
+struct PointerChase {
+    next: Vec<u32>,
+    end: Option<usize>,
+}
+
+impl PointerChase {
+    fn new(elements: usize, rng: &mut Rng) -> Self {
+        let mut next: Vec<u32> = (0..elements.max(1) as u32).collect();
+
+        // Sattolo's algorithm yields one cycle covering every element.
+        for i in (1..next.len()).rev() {
+            let j = rng.below(i);
+            next.swap(i, j);
+        }
+
+        Self { next, end: None }
+    }
+}
+
+impl Workload for PointerChase {
+    fn run(&mut self, progress: &mut Progress) {
+        let mut index = 0;
+
+        for step in 0..self.next.len() as u64 {
+            index = self.next[index] as usize;
+
+            if step.is_multiple_of(REPORT_EVERY) {
+                progress.tick(step);
+            }
+        }
+
+        self.end = Some(black_box(index));
+    }
+
+    fn memory(&self) -> &[u32] {
+        &self.next
+    }
+
+    // One full lap around the single cycle ends where it started.
+    fn verify(&self) -> Result<String, String> {
+        match self.end {
+            Some(0) => Ok(format!("back at 0 after {} steps", self.next.len())),
+            Some(end) => Err(format!("ended at {} instead of 0", end)),
+            None => Err("never ran".to_string()),
+        }
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+struct Hash {
+    data: Vec<u32>,
+    hash: Option<u64>,
+}
+
+impl Workload for Hash {
+    fn run(&mut self, progress: &mut Progress) {
+        // FNV-1a over the raw bytes.
+        let mut hash = FNV_OFFSET;
+
+        for (i, value) in self.data.iter().enumerate() {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+
+            if (i as u64).is_multiple_of(REPORT_EVERY) {
+                progress.tick(i as u64);
+            }
+        }
+
+        self.hash = Some(black_box(hash));
+    }
+
+    fn memory(&self) -> &[u32] {
+        &self.data
+    }
+
+    fn verify(&self) -> Result<String, String> {
+        let expected = self
+            .data
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            });
+
+        match self.hash {
+            Some(hash) if hash == expected => Ok(format!("hash {:016x}", hash)),
+            Some(hash) => Err(format!("hash {:016x}, expected {:016x}", hash, expected)),
+            None => Err("never ran".to_string()),
+        }
+    }
+}
+
+const BANDWIDTH_PASSES: u64 = 8;
+const BANDWIDTH_CHUNK: usize = 1 << 16;
+
+// Reads the whole array over and over with next to no work per element, so
+// the time goes into getting it out of memory and depends on where it lives.
+struct Bandwidth {
+    data: Vec<u32>,
+    sum: Option<u64>,
+}
+
+impl Workload for Bandwidth {
+    fn run(&mut self, progress: &mut Progress) {
+        let mut sum = 0u64;
+        let mut done = 0;
+
+        for _ in 0..BANDWIDTH_PASSES {
+            for chunk in self.data.chunks(BANDWIDTH_CHUNK) {
+                let chunk_sum = chunk.iter().map(|&value| value as u64).sum::<u64>();
+                sum = sum.wrapping_add(chunk_sum);
+
+                done += chunk.len() as u64;
+                progress.tick(done);
+            }
+        }
+
+        self.sum = Some(black_box(sum));
+    }
+
+    fn memory(&self) -> &[u32] {
+        &self.data
+    }
+
+    fn bytes(&self) -> u64 {
+        BANDWIDTH_PASSES * std::mem::size_of_val(&self.data[..]) as u64
+    }
+
+    // The data is 0..n, so every pass adds the same closed form.
+    fn verify(&self) -> Result<String, String> {
+        let n = self.data.len() as u64;
+        let expected = (n * n.saturating_sub(1) / 2).wrapping_mul(BANDWIDTH_PASSES);
+
+        match self.sum {
+            Some(sum) if sum == expected => Ok(format!("sum {}", sum)),
+            Some(sum) => Err(format!("sum {}, expected {}", sum, expected)),
+            None => Err("never ran".to_string()),
+        }
+    }
+}
+
+const SLEEP_CHUNK: usize = 100_000;
+const SLEEP_TIME: Duration = Duration::from_millis(1);
+
+// Mostly blocked like an I/O bound thread, with a short burst of work after
+// every wake-up.
+struct Sleep {
+    data: Vec<u32>,
+    sum: Option<u64>,
+}
+
+impl Workload for Sleep {
+    fn run(&mut self, progress: &mut Progress) {
+        let mut sum = 0u64;
+
+        for (i, chunk) in self.data.chunks(SLEEP_CHUNK).enumerate() {
+            std::thread::sleep(SLEEP_TIME);
+            sum += chunk.iter().map(|&value| value as u64).sum::<u64>();
+
+            progress.tick((i * SLEEP_CHUNK + chunk.len()) as u64);
+        }
+
+        self.sum = Some(black_box(sum));
+    }
+
+    fn memory(&self) -> &[u32] {
+        &self.data
+    }
+
+    // The data is 0..n, so the sum is known in closed form.
+    fn verify(&self) -> Result<String, String> {
+        let n = self.data.len() as u64;
+        let expected = n * n.saturating_sub(1) / 2;
+
+        match self.sum {
+            Some(sum) if sum == expected => Ok(format!("sum {}", sum)),
+            Some(sum) => Err(format!("sum {}, expected {}", sum, expected)),
+            None => Err("never ran".to_string()),
+        }
+    }
+}
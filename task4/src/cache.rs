@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cache {
+    // Evicted from the page cache before every run.
+    Cold,
+    // Read once before the first run, so every run finds it cached.
+    Warm,
+}
+
+impl Cache {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cold => "cold",
+            Self::Warm => "warm",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Cold, Self::Warm]
+            .into_iter()
+            .find(|cache| cache.name() == value)
+    }
+}
+
+// Drops the file's pages from the page cache. Only clean pages go, so a file
+// that was just written is flushed to disk first.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn evict(path: &str) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(path)?;
+    file.sync_data()?;
+
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) } {
+        0 => Ok(()),
+        error => Err(io::Error::from_raw_os_error(error)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn evict(_: &str) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+pub fn preload(path: &str) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1 << 20];
+
+    while file.read(&mut buffer)? > 0 {}
+    Ok(())
+}
+
+// How much of the file is in the page cache, from 0 to 1, where that can be
+// found out.
+#[cfg(unix)]
+pub fn resident(path: &str) -> Option<f64> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len() as usize;
+
+    if length == 0 {
+        return Some(1.0);
+    }
+
+    unsafe {
+        let page = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let address = libc::mmap(
+            std::ptr::null_mut(),
+            length,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if address == libc::MAP_FAILED {
+            return None;
+        }
+
+        // Mapping a file doesn't read it; mincore only looks at the cache.
+        let mut pages = vec![0u8; length.div_ceil(page)];
+        let result = libc::mincore(address, length, pages.as_mut_ptr() as _);
+        libc::munmap(address, length);
+
+        if result != 0 {
+            return None;
+        }
+
+        let cached = pages.iter().filter(|&&page| page & 1 != 0).count();
+        Some(cached as f64 / pages.len() as f64)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn resident(_: &str) -> Option<f64> {
+    None
+}
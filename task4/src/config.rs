@@ -0,0 +1,264 @@
+use crate::cache::Cache;
+use crate::count::Mode;
+use crate::generate::Content;
+use crate::histogram::Engine;
+use crate::reader::Backend;
+use crate::strategy::Strategy;
+
+pub const USAGE: &str = "\
+usage: task4 [options]
+
+  --file PATH          file to read (default ./test_file.txt)
+  --strategy LIST      how the file is read: buffered, std's BufReader, pread,
+                       positioned reads, mmap, the file mapped with madvise
+                       hints (Unix), direct, bypassing the page cache with
+                       aligned buffers, async, reads in flight on the backend,
+                       or threads, a range of the file per thread; a list or
+                       all compares them (default async)
+  --backend B          backend of the async strategy: overlapped (Windows),
+                       io-uring (Linux) or pool, positioned reads on a thread
+                       pool (default: the native one)
+  --threads N          threads of the pool backend and of the threads strategy
+                       (default 4)
+  --depth LIST         reads the async strategy keeps in flight at once, e.g.
+                       1,4,16 to compare several queue depths, at most 64 on
+                       the overlapped backend (default 1)
+  --cache C            cold, evicting the file from the page cache before every
+                       run (Linux and FreeBSD; elsewhere only direct reads,
+                       which bypass it, run cold), or warm, reading it once
+                       before the first (default warm)
+  --runs N             runs of every combination, reported as mean and standard
+                       deviation (default 3)
+  --sizes LIST         buffer sizes to read with (default 4,8,...,524288)
+  --count M            bytes, a histogram of byte values, or chars, Unicode
+                       scalars of UTF-8 text with invalid sequences counted
+                       (default bytes)
+  --engine LIST        how bytes are counted: map, a BTreeMap as before, scalar,
+                       one table, interleaved, several tables, or unrolled16,
+                       16 bytes per load spread over 16 tables; a list or all
+                       compares them (default unrolled16). Unlike before, no
+                       engine folds ASCII letters to lowercase: every byte
+                       value counts as is
+  --counts PATH        write what was counted to PATH
+  --verify             check the byte counts of every run against FILE.bytes,
+                       as generate leaves it next to the file, and exit with 1
+                       when any of them differ
+
+usage: task4 generate [options]
+
+  --output PATH        file to write, with its byte histogram in PATH.bytes
+                       (default ./test_file.txt)
+  --size N             bytes to write, with an optional K, M, G or T suffix for
+                       binary multiples, e.g. 64K or 20G (default 2M)
+  --content C          random, random bytes, english, English-like text from a
+                       Markov model, skewed, mostly one letter, or
+                       multilingual, UTF-8 text in many scripts (default english)
+  --seed N             seed of the generator; the same one makes the same file
+                       (default 1)
+";
+
+pub struct Config {
+    pub path: String,
+    pub strategies: Vec<Strategy>,
+    pub backend: Backend,
+    pub threads: usize,
+    pub depths: Vec<usize>,
+    pub sizes: Vec<usize>,
+    pub cache: Cache,
+    pub runs: usize,
+    pub mode: Mode,
+    pub engines: Vec<Engine>,
+    pub counts: Option<String>,
+    pub verify: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: "./test_file.txt".to_string(),
+            strategies: vec![Strategy::Async],
+            backend: Backend::native(),
+            threads: 4,
+            depths: vec![1],
+            sizes: (2..20).map(|i| 1 << i).collect(),
+            cache: Cache::Warm,
+            runs: 3,
+            mode: Mode::Bytes,
+            engines: vec![Engine::Unrolled],
+            counts: None,
+            verify: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut config = Self::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+
+            if arg == "--verify" {
+                config.verify = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .unwrap_or_else(|| panic!("{} needs a value\n{}", arg, USAGE));
+
+            let number = || -> usize {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{} needs a number", arg))
+            };
+
+            match arg.as_str() {
+                "--file" => config.path = value,
+                "--strategy" if value == "all" => config.strategies = Strategy::ALL.to_vec(),
+                "--strategy" => {
+                    config.strategies = value
+                        .split(',')
+                        .map(|strategy| {
+                            Strategy::parse(strategy).unwrap_or_else(|| {
+                                panic!("unknown strategy: {}\n{}", strategy, USAGE)
+                            })
+                        })
+                        .collect()
+                }
+                "--backend" => {
+                    config.backend = Backend::parse(&value)
+                        .unwrap_or_else(|| panic!("unknown backend: {}\n{}", value, USAGE))
+                }
+                "--threads" => config.threads = number(),
+                "--depth" => config.depths = list(&value, "queue depth"),
+                "--sizes" => config.sizes = list(&value, "buffer size"),
+                "--cache" => {
+                    config.cache = Cache::parse(&value)
+                        .unwrap_or_else(|| panic!("unknown cache mode: {}\n{}", value, USAGE))
+                }
+                "--runs" => config.runs = number().max(1),
+                "--count" => {
+                    config.mode = Mode::parse(&value)
+                        .unwrap_or_else(|| panic!("unknown count mode: {}\n{}", value, USAGE))
+                }
+                "--engine" if value == "all" => config.engines = Engine::ALL.to_vec(),
+                "--engine" => {
+                    config.engines = value
+                        .split(',')
+                        .map(|engine| {
+                            Engine::parse(engine)
+                                .unwrap_or_else(|| panic!("unknown engine: {}\n{}", engine, USAGE))
+                        })
+                        .collect()
+                }
+                "--counts" => config.counts = Some(value),
+                other => panic!("unknown argument: {}\n{}", other, USAGE),
+            }
+        }
+
+        if config.verify && config.mode != Mode::Bytes {
+            panic!("--verify checks byte counts, it can't go with --count chars");
+        }
+
+        // Overlapped reads wait for their events all at once, and
+        // WaitForMultipleObjects takes no more than MAXIMUM_WAIT_OBJECTS.
+        if config.backend == Backend::Overlapped && config.strategies.contains(&Strategy::Async) {
+            if let Some(depth) = config
+                .depths
+                .iter()
+                .find(|&&depth| depth > MAX_OVERLAPPED_DEPTH)
+            {
+                panic!(
+                    "queue depth {} is more than the overlapped backend can wait for, at most {}",
+                    depth, MAX_OVERLAPPED_DEPTH
+                );
+            }
+        }
+
+        config
+    }
+}
+
+const MAX_OVERLAPPED_DEPTH: usize = 64;
+
+// A comma separated list of positive numbers.
+fn list(value: &str, what: &str) -> Vec<usize> {
+    value
+        .split(',')
+        .map(|item| match item.parse() {
+            Ok(number) if number > 0 => number,
+            _ => panic!("bad {}: {}", what, item),
+        })
+        .collect()
+}
+
+pub struct Generation {
+    pub path: String,
+    pub size: u64,
+    pub content: Content,
+    pub seed: u64,
+}
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self {
+            path: "./test_file.txt".to_string(),
+            size: 2 << 20,
+            content: Content::English,
+            seed: 1,
+        }
+    }
+}
+
+impl Generation {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut generation = Self::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .unwrap_or_else(|| panic!("{} needs a value\n{}", arg, USAGE));
+
+            match arg.as_str() {
+                "--output" => generation.path = value,
+                "--size" => {
+                    generation.size = size(&value).unwrap_or_else(|| panic!("bad size: {}", value))
+                }
+                "--content" => {
+                    generation.content = Content::parse(&value)
+                        .unwrap_or_else(|| panic!("unknown content: {}\n{}", value, USAGE))
+                }
+                "--seed" => {
+                    generation.seed = value
+                        .parse()
+                        .unwrap_or_else(|_| panic!("--seed needs a number"))
+                }
+                other => panic!("unknown argument: {}\n{}", other, USAGE),
+            }
+        }
+
+        generation
+    }
+}
+
+// A number of bytes, like 4096, 64K or 20G.
+fn size(value: &str) -> Option<u64> {
+    let (number, shift) = match value.as_bytes().last()?.to_ascii_uppercase() {
+        b'K' => (&value[..value.len() - 1], 10),
+        b'M' => (&value[..value.len() - 1], 20),
+        b'G' => (&value[..value.len() - 1], 30),
+        b'T' => (&value[..value.len() - 1], 40),
+        _ => (value, 0),
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::histogram::{self, Engine, Histogram};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Bytes,
+    Chars,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bytes => "bytes",
+            Self::Chars => "chars",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Bytes, Self::Chars]
+            .into_iter()
+            .find(|mode| mode.name() == value)
+    }
+}
+
+// Counts either every byte value or every Unicode scalar of a file that
+// arrives in chunks, in any order. A character split between two chunks is
+// put back together as soon as both are in: until then the end of one chunk
+// or the start of the next is kept aside, by the offset between them.
+pub struct Counter {
+    mode: Mode,
+    bytes: Histogram,
+    chars: BTreeMap<char, u64>,
+    invalid: u64,
+    boundaries: BTreeMap<u64, Boundary>,
+}
+
+// What is known about the bytes around a boundary between two chunks.
+#[derive(Default)]
+struct Boundary {
+    // An unfinished character the chunk before ends with, once it's in.
+    tail: Option<Vec<u8>>,
+    // The continuation bytes the chunk after starts with, once it's in, and
+    // where that chunk ends if they are all there is to it.
+    head: Option<(Vec<u8>, Option<u64>)>,
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+impl Counter {
+    pub fn new(mode: Mode, engine: Engine) -> Self {
+        Self {
+            mode,
+            bytes: Histogram::new(engine),
+            chars: BTreeMap::new(),
+            invalid: 0,
+            boundaries: BTreeMap::new(),
+        }
+    }
+
+    // A counter like this one with nothing counted yet, for counting another
+    // part of the same file.
+    pub fn empty(&self) -> Self {
+        Self {
+            mode: self.mode,
+            bytes: self.bytes.empty(),
+            chars: BTreeMap::new(),
+            invalid: 0,
+            boundaries: BTreeMap::new(),
+        }
+    }
+
+    // Adds what another part of the file counted, putting together the
+    // characters split between the parts.
+    pub fn merge(&mut self, other: Self) {
+        self.bytes.merge(&other.bytes);
+
+        for (character, count) in other.chars {
+            *self.chars.entry(character).or_insert(0) += count;
+        }
+
+        self.invalid += other.invalid;
+
+        for (offset, boundary) in other.boundaries {
+            self.arrive(offset, boundary);
+        }
+    }
+
+    pub fn count(&mut self, offset: u64, data: &[u8]) {
+        match self.mode {
+            Mode::Bytes => self.bytes.add(data),
+            Mode::Chars => self.count_chars(offset, data),
+        }
+    }
+
+    fn count_chars(&mut self, offset: u64, data: &[u8]) {
+        // No character is longer than 4 bytes, so at most 3 of them can
+        // belong to one that started in the previous chunk.
+        let head = data
+            .iter()
+            .take(3)
+            .take_while(|&&byte| is_continuation(byte))
+            .count();
+
+        let end = offset + data.len() as u64;
+
+        // A chunk that is nothing but continuation bytes has no end of its
+        // own: what comes before it carries on through it to the next one.
+        if head == data.len() {
+            self.arrive(
+                offset,
+                Boundary {
+                    tail: None,
+                    head: Some((data.to_vec(), Some(end))),
+                },
+            );
+            return;
+        }
+
+        let tail = self.decode(&data[head..]).unwrap_or(0);
+
+        self.arrive(
+            offset,
+            Boundary {
+                tail: None,
+                head: Some((data[..head].to_vec(), None)),
+            },
+        );
+        self.arrive(
+            end,
+            Boundary {
+                tail: Some(data[data.len() - tail..].to_vec()),
+                head: None,
+            },
+        );
+    }
+
+    // Adds what a chunk says about a boundary, and settles the boundary once
+    // both of its sides are known.
+    fn arrive(&mut self, offset: u64, side: Boundary) {
+        let mut next = Some((offset, side));
+
+        while let Some((offset, side)) = next.take() {
+            let boundary = self.boundaries.entry(offset).or_default();
+            boundary.tail = boundary.tail.take().or(side.tail);
+            boundary.head = boundary.head.take().or(side.head);
+
+            if boundary.tail.is_some() && boundary.head.is_some() {
+                let boundary = self.boundaries.remove(&offset).unwrap();
+                next = self.join(boundary);
+            }
+        }
+    }
+
+    // The bytes of a boundary, either settled or carried on to the end of a
+    // chunk made only of continuation bytes.
+    fn join(&mut self, boundary: Boundary) -> Option<(u64, Boundary)> {
+        let mut bytes = boundary.tail.unwrap_or_default();
+        let (head, end) = boundary.head.unwrap_or_default();
+        bytes.extend(head);
+
+        match end {
+            Some(end) => Some((
+                end,
+                Boundary {
+                    tail: Some(bytes),
+                    head: None,
+                },
+            )),
+            None => {
+                self.settle(&bytes);
+                None
+            }
+        }
+    }
+
+    // Counts every complete character and every invalid sequence. Returns
+    // the length of an unfinished character at the very end, if any.
+    fn decode(&mut self, mut data: &[u8]) -> Option<usize> {
+        loop {
+            match std::str::from_utf8(data) {
+                Ok(text) => {
+                    self.add(text);
+                    return None;
+                }
+                Err(error) => {
+                    let (valid, rest) = data.split_at(error.valid_up_to());
+                    self.add(std::str::from_utf8(valid).unwrap());
+
+                    match error.error_len() {
+                        Some(length) => {
+                            self.invalid += 1;
+                            data = &rest[length..];
+                        }
+                        None => return Some(rest.len()),
+                    }
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, text: &str) {
+        for character in text.chars() {
+            *self.chars.entry(character).or_insert(0) += 1;
+        }
+    }
+
+    // Settles the boundaries still missing a side: the start of the file has
+    // nothing before it and the end nothing after. A character cut off by the
+    // end of the file and continuation bytes with nothing to continue are both
+    // invalid.
+    pub fn finish(&mut self) {
+        while let Some((_, boundary)) = self.boundaries.pop_first() {
+            if let Some((end, carried)) = self.join(boundary) {
+                self.arrive(end, carried);
+            }
+        }
+    }
+
+    fn settle(&mut self, bytes: &[u8]) {
+        if self.decode(bytes).is_some() {
+            self.invalid += 1;
+        }
+    }
+
+    // A histogram of byte values, or one "U+XXXX count" line per character
+    // followed by the number of invalid sequences.
+    pub fn report(&self) -> String {
+        match self.mode {
+            Mode::Bytes => histogram::report(&self.bytes.table()),
+            Mode::Chars => {
+                let mut report = String::new();
+
+                for (&character, count) in &self.chars {
+                    _ = writeln!(report, "U+{:04X} {}", character as u32, count);
+                }
+                _ = writeln!(report, "invalid {}", self.invalid);
+
+                report
+            }
+        }
+    }
+
+    pub fn invalid(&self) -> u64 {
+        self.invalid
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::config::Generation;
+use crate::histogram::{self, Table};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Content {
+    Random,
+    English,
+    Skewed,
+    Multilingual,
+}
+
+impl Content {
+    pub const ALL: [Self; 4] = [
+        Self::Random,
+        Self::English,
+        Self::Skewed,
+        Self::Multilingual,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::English => "english",
+            Self::Skewed => "skewed",
+            Self::Multilingual => "multilingual",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|content| content.name() == value)
+    }
+}
+
+// xorshift64*, good enough to make test files reproducibly.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Scaled rather than taken modulo the bound, which costs a division for
+    // every byte of a file that may be tens of gigabytes.
+    fn below(&mut self, bound: usize) -> usize {
+        (((self.next() >> 32) * bound as u64) >> 32) as usize
+    }
+}
+
+// What the English model learns from. The text wraps around, so every pair of
+// characters in it has at least one that follows.
+const CORPUS: &str = "\
+it was late in the evening when the letter finally arrived at the house on \
+the hill. nobody had expected it, least of all the old man who lived there \
+alone with his books and his garden. he read it twice by the window, folded \
+it carefully and put it back into the envelope. then he sat for a long time \
+without moving, watching the light fade over the fields and the river. in \
+the morning he packed a small bag, locked the door and walked down to the \
+station, where the first train of the day was already waiting. the journey \
+would take most of the day, and he spent it looking out at the country going \
+by, thinking about the people he had known there and the years that had \
+passed since he had seen any of them. ";
+
+// Every pair of characters in the corpus mapped to each character that follows
+// it there, repeats included, so picking from the list follows the frequencies.
+// The lists sit one after the other, with `starts` marking where each begins.
+struct Markov {
+    starts: Vec<usize>,
+    next: Vec<u8>,
+    previous: u8,
+    current: u8,
+}
+
+impl Markov {
+    fn new() -> Self {
+        let corpus = CORPUS.as_bytes();
+        let mut lists = vec![vec![]; 256 * 256];
+
+        for i in 0..corpus.len() {
+            let context = corpus[i] as usize * 256 + corpus[(i + 1) % corpus.len()] as usize;
+            lists[context].push(corpus[(i + 2) % corpus.len()]);
+        }
+
+        let mut starts = vec![0];
+        for list in &lists {
+            starts.push(starts.last().unwrap() + list.len());
+        }
+
+        Self {
+            starts,
+            next: lists.concat(),
+            previous: corpus[0],
+            current: corpus[1],
+        }
+    }
+
+    fn step(&mut self, rng: &mut Rng) -> u8 {
+        let context = self.previous as usize * 256 + self.current as usize;
+        let (start, end) = (self.starts[context], self.starts[context + 1]);
+        let byte = self.next[start + rng.below(end - start)];
+
+        self.previous = self.current;
+        self.current = byte;
+        byte
+    }
+}
+
+// Words from a few scripts, with one to four bytes per character.
+const WORDS: [&str; 16] = [
+    "hello",
+    "naïve",
+    "café",
+    "Größe",
+    "Ελληνικά",
+    "русский",
+    "текст",
+    "日本語",
+    "テキスト",
+    "한국어",
+    "中文",
+    "עברית",
+    "العربية",
+    "हिन्दी",
+    "😀🚀",
+    "𝄞",
+];
+
+struct Generator {
+    content: Content,
+    rng: Rng,
+    markov: Option<Markov>,
+}
+
+impl Generator {
+    fn new(content: Content, seed: u64) -> Self {
+        Self {
+            content,
+            rng: Rng::new(seed),
+            markov: (content == Content::English).then(Markov::new),
+        }
+    }
+
+    // Appends at least one and at most `room` bytes. Text never ends in the
+    // middle of a character: a word that doesn't fit is left out.
+    fn piece(&mut self, out: &mut Vec<u8>, room: usize) {
+        match self.content {
+            Content::Random => {
+                let length = room.min(4096);
+
+                for start in (0..length).step_by(8) {
+                    let bytes = self.rng.next().to_le_bytes();
+                    out.extend_from_slice(&bytes[..(length - start).min(8)]);
+                }
+            }
+            Content::English => {
+                let markov = self.markov.as_mut().unwrap();
+                let length = room.min(4096);
+                let mut written = 0;
+
+                while written < length {
+                    let byte = markov.step(&mut self.rng);
+                    out.push(byte);
+                    written += 1;
+
+                    // Turn the end of a sentence into a line now and then.
+                    if byte == b'.' && written < length && self.rng.below(4) == 0 {
+                        markov.previous = b'.';
+                        markov.current = b' ';
+                        out.push(b'\n');
+                        written += 1;
+                    }
+                }
+            }
+            Content::Skewed => {
+                // Nine bytes out of ten are the same letter; the rest are
+                // spread over the other lowercase ones.
+                for _ in 0..room.min(4096) {
+                    out.push(match self.rng.below(10) {
+                        0 => b'a' + self.rng.below(26) as u8,
+                        _ => b'e',
+                    });
+                }
+            }
+            Content::Multilingual => {
+                let word = WORDS[self.rng.below(WORDS.len())];
+                let separator = if self.rng.below(10) == 0 { b'\n' } else { b' ' };
+
+                if word.len() < room {
+                    out.extend_from_slice(word.as_bytes());
+                    out.push(separator);
+                } else {
+                    out.push(b' ');
+                }
+            }
+        }
+    }
+}
+
+const BLOCK: usize = 1 << 20;
+
+// Writes `size` bytes of the content to the file and its byte histogram, in
+// the format of --counts, next to it.
+pub fn command(generation: Generation) {
+    let mut file = BufWriter::new(File::create(&generation.path).expect("can't create file"));
+    let mut generator = Generator::new(generation.content, generation.seed);
+    let mut table: Table = [0; 256];
+    let mut block = Vec::with_capacity(BLOCK + 4096);
+    let mut written = 0;
+
+    while written < generation.size {
+        let left = generation.size - written;
+
+        let length = left.min(BLOCK as u64) as usize;
+
+        while block.len() < length {
+            let room = length - block.len();
+            let start = block.len();
+
+            generator.piece(&mut block, room);
+            assert!(block.len() - start <= room, "generated too much");
+        }
+
+        for &byte in &block {
+            table[byte as usize] += 1;
+        }
+
+        file.write_all(&block).expect("can't write file");
+        written += block.len() as u64;
+        block.clear();
+    }
+
+    file.flush().expect("can't write file");
+
+    let expected = format!("{}.bytes", generation.path);
+    std::fs::write(&expected, histogram::report(&table)).expect("can't write histogram");
+
+    println!(
+        "wrote {} bytes of {} content with seed {} to {}, histogram in {}",
+        written,
+        generation.content.name(),
+        generation.seed,
+        generation.path,
+        expected
+    );
+}
@@ -1,71 +1,326 @@
-use std::collections::BTreeMap;
+mod cache;
+mod config;
+mod count;
+mod generate;
+mod histogram;
+#[cfg(windows)]
+mod overlapped;
+mod pool;
+mod reader;
+mod strategy;
+#[cfg(target_os = "linux")]
+mod uring;
 
-use windows::core::*;
-// use windows::Win32::Foundation::*;
-use windows::Win32::Storage::FileSystem::*;
-use windows::Win32::System::Threading::*;
-use windows::Win32::System::IO::*;
+use std::fmt::Write as _;
+use std::io::Read;
+use std::time::Duration;
+
+use cache::Cache;
+use config::{Config, Generation};
+use count::{Counter, Mode};
+use histogram::{Engine, Histogram};
+use strategy::ReadStrategy;
 
 fn main() {
-    for i in 2..20 {
-        let buffer_size = 1 << i;
-        let elapsed = read_file(buffer_size);
-        println!("buffer size: {}, time elapsed: {:?}", buffer_size, elapsed);
+    let mut args = std::env::args().skip(1).peekable();
+
+    if args.peek().map(String::as_str) == Some("generate") {
+        args.next();
+        generate::command(Generation::parse(args));
+        return;
     }
-}
 
-fn read_file(buffer_size: usize) -> std::time::Duration {
-    let file = unsafe {
-        CreateFileW(
-            w!("./test_file.txt"),
-            FILE_GENERIC_READ,
-            FILE_SHARE_READ,
-            None,
-            OPEN_EXISTING,
-            FILE_FLAG_OVERLAPPED,
-            None,
-        )
-        .expect("can't open file")
+    let config = Config::parse(args);
+
+    println!(
+        "backend: {}, counting: {}, cache: {}, runs: {}",
+        config.backend.name(),
+        config.mode.name(),
+        config.cache.name(),
+        config.runs
+    );
+
+    // Engines only count bytes; characters are counted one way.
+    let engines = match config.mode {
+        Mode::Bytes => &config.engines[..],
+        Mode::Chars => &config.engines[..1],
     };
 
-    let mut overlapped = OVERLAPPED::default();
+    if config.mode == Mode::Bytes {
+        counting_speed(&config.path, engines);
+    }
+
+    // Where the page cache can't be emptied, only reads that bypass it can run
+    // cold.
+    let unevictable = match config.cache {
+        Cache::Cold => cache::evict(&config.path).err(),
+        Cache::Warm => None,
+    };
 
-    let mut map = BTreeMap::new();
-    let mut buffer = vec![0; buffer_size];
+    // A strategy that can't read the file here is left out of the table
+    // rather than stopping the others.
+    let strategies: Vec<Box<dyn ReadStrategy>> = config
+        .strategies
+        .iter()
+        .flat_map(|strategy| strategy.create(config.backend, &config.depths, config.threads))
+        .filter(|strategy| {
+            let reason = strategy.unsupported(&config.path).or_else(|| {
+                let error = unevictable.as_ref().filter(|_| strategy.cached())?;
+                Some(format!(
+                    "can't evict the file from the page cache to read it cold ({})",
+                    error
+                ))
+            });
 
-    let timer = std::time::Instant::now();
+            let Some(reason) = reason else {
+                return true;
+            };
 
-    loop {
-        unsafe {
-            _ = ReadFile(file, Some(&mut buffer), None, Some(&mut overlapped));
-            WaitForSingleObject(overlapped.hEvent, u32::MAX);
+            println!("strategy: {}, unsupported: {}", strategy.name(), reason);
+            false
+        })
+        .collect();
 
-            let mut bytes_copied = 0;
-            let res = GetOverlappedResult(file, &overlapped, &mut bytes_copied, false);
-            overlapped.Anonymous.Anonymous.Offset += bytes_copied;
-            if !res.as_bool() {
-                break;
+    match config.cache {
+        Cache::Warm => {
+            cache::preload(&config.path).expect("can't preload file");
+            print_resident(&config.path, "after preloading");
+        }
+        Cache::Cold => {
+            if unevictable.is_none() {
+                print_resident(&config.path, "after evicting");
             }
         }
+    }
 
-        buffer.make_ascii_lowercase();
+    // The histogram generate wrote along with the file.
+    let histogram = format!("{}.bytes", config.path);
+    let expected = config.verify.then(|| {
+        std::fs::read_to_string(&histogram)
+            .unwrap_or_else(|error| panic!("can't read {} ({})", histogram, error))
+    });
 
-        for &byte in &buffer {
-            let character = byte as char;
+    let sizes = &config.sizes;
+    let mut columns = vec![];
+    let mut throughput = vec![];
+    let mut report = None;
+    let mut mismatches = 0;
 
-            if let Some(count) = map.get_mut(&character) {
-                *count += 1;
-            } else {
-                map.insert(character, 1);
+    for strategy in &strategies {
+        for &engine in engines {
+            columns.push(match config.mode {
+                Mode::Bytes => format!("{} {}", strategy.name(), engine.name()),
+                Mode::Chars => strategy.name(),
+            });
+
+            for &buffer_size in sizes {
+                let mut rates = vec![];
+
+                for run in 1..=config.runs {
+                    // Direct reads never look into the page cache, so there is
+                    // nothing to evict for them.
+                    if config.cache == Cache::Cold && strategy.cached() {
+                        cache::evict(&config.path).unwrap_or_else(|error| {
+                            panic!(
+                                "can't evict the file from the page cache ({}); \
+                                 --cache warm reads without evicting",
+                                error
+                            )
+                        });
+                    }
+
+                    let (bytes, elapsed, counter) =
+                        read_file(&config, strategy.as_ref(), engine, buffer_size);
+                    let rate = megabytes_per_second(bytes, elapsed);
+
+                    println!(
+                        "strategy: {}, engine: {}, buffer size: {}, run: {}, time elapsed: {:?}, {:.1} MB/s",
+                        strategy.name(),
+                        engine.name(),
+                        buffer_size,
+                        run,
+                        elapsed,
+                        rate
+                    );
+
+                    rates.push(rate);
+
+                    // Every run reads the same file, so all of them have to agree,
+                    // with the histogram too when there is one to check against.
+                    let counts = counter.report();
+                    if let Some(expected) = &expected {
+                        if counts != *expected {
+                            println!(
+                                "{} with {} at buffer size {}, run {}: byte counts differ from {}",
+                                strategy.name(),
+                                engine.name(),
+                                buffer_size,
+                                run,
+                                histogram
+                            );
+                            mismatches += 1;
+                        }
+                    }
+                    match &report {
+                        None => report = Some(counts),
+                        Some(_) if expected.is_some() => {}
+                        Some(first) => assert!(
+                            *first == counts,
+                            "{} with {} at buffer size {} counted differently",
+                            strategy.name(),
+                            engine.name(),
+                            buffer_size
+                        ),
+                    }
+
+                    if config.mode == Mode::Chars && counter.invalid() > 0 {
+                        println!("invalid UTF-8 sequences: {}", counter.invalid());
+                    }
+                }
+
+                let (mean, deviation) = spread(&rates);
+
+                if config.runs > 1 {
+                    println!(
+                        "{:.1} MB/s on average, standard deviation {:.1} MB/s ({:.1}%)",
+                        mean,
+                        deviation,
+                        100.0 * deviation / mean
+                    );
+                }
+
+                throughput.push((mean, deviation));
             }
         }
     }
 
-    // println!("count of each character in text:");
+    if columns.len() > 1 {
+        print!("\n{}", table(sizes, &columns, &throughput, config.runs));
+    }
+
+    if let Some(report) = report {
+        if let Some(path) = &config.counts {
+            std::fs::write(path, report).expect("can't write counts");
+        }
+    }
+
+    if config.verify {
+        if mismatches > 0 {
+            println!(
+                "verification failed: {} runs differ from {}",
+                mismatches, histogram
+            );
+            std::process::exit(1);
+        }
+        println!("byte counts of every run match {}", histogram);
+    }
+}
 
-    // for (character, count) in map {
-    //     println!("{:?}: {}", character, count);
-    // }
+// Whether the cache mode did what it should, where the page cache can be
+// looked into.
+fn print_resident(path: &str, when: &str) {
+    if let Some(resident) = cache::resident(path) {
+        println!("{:.0}% of the file cached {}", 100.0 * resident, when);
+    }
+}
+
+// Mean and sample standard deviation.
+fn spread(rates: &[f64]) -> (f64, f64) {
+    let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+
+    if rates.len() < 2 {
+        return (mean, 0.0);
+    }
+
+    let variance = rates
+        .iter()
+        .map(|rate| (rate - mean) * (rate - mean))
+        .sum::<f64>()
+        / (rates.len() - 1) as f64;
+
+    (mean, variance.sqrt())
+}
+
+fn megabytes_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64()
+}
+
+// The most of the file counting_speed holds in memory at once.
+const SAMPLE: u64 = 64 << 20;
+const SAMPLE_CHUNK: usize = 1 << 20;
+
+// How fast each engine counts data that is already in memory, the most any
+// read can get out of it. The start of the file stands in for all of it, so
+// files bigger than memory can be measured too. Whatever this brings into the
+// page cache is evicted again before a cold run.
+fn counting_speed(path: &str, engines: &[Engine]) {
+    let mut data = vec![];
+    std::fs::File::open(path)
+        .expect("can't open file")
+        .take(SAMPLE)
+        .read_to_end(&mut data)
+        .expect("can't read file");
+
+    for &engine in engines {
+        let mut histogram = Histogram::new(engine);
+
+        // Chunks the size of a large read, rather than one call over it all.
+        let timer = std::time::Instant::now();
+        for chunk in data.chunks(SAMPLE_CHUNK) {
+            histogram.add(chunk);
+        }
+        let elapsed = timer.elapsed();
+
+        println!(
+            "engine: {}, counting in memory: {:.1} MB/s",
+            engine.name(),
+            megabytes_per_second(data.len() as u64, elapsed)
+        );
+    }
+}
+
+// MB/s with one row per buffer size and one column per strategy and engine,
+// with the standard deviation over the runs when there are several.
+fn table(sizes: &[usize], columns: &[String], throughput: &[(f64, f64)], runs: usize) -> String {
+    let widths: Vec<_> = columns.iter().map(|column| column.len().max(16)).collect();
+    let mut table = format!("{:>12}", "MB/s");
+
+    for (column, width) in columns.iter().zip(&widths) {
+        _ = write!(table, " {:>width$}", column, width = width);
+    }
+    table.push('\n');
+
+    for (row, size) in sizes.iter().enumerate() {
+        _ = write!(table, "{:>12}", size);
+
+        for (column, width) in widths.iter().enumerate() {
+            let (mean, deviation) = throughput[column * sizes.len() + row];
+            let cell = if runs > 1 {
+                format!("{:.1} ±{:.1}", mean, deviation)
+            } else {
+                format!("{:.1}", mean)
+            };
+            _ = write!(table, " {:>width$}", cell, width = width);
+        }
+        table.push('\n');
+    }
+
+    table
+}
+
+// Counts the file once. Returns how many bytes were read, how long it took and
+// what was counted.
+fn read_file(
+    config: &Config,
+    strategy: &dyn ReadStrategy,
+    engine: Engine,
+    buffer_size: usize,
+) -> (u64, Duration, Counter) {
+    let mut counter = Counter::new(config.mode, engine);
+
+    let timer = std::time::Instant::now();
+    let bytes = strategy.read(&config.path, buffer_size, &mut counter);
+    counter.finish();
 
-    timer.elapsed()
+    (bytes, timer.elapsed(), counter)
 }
@@ -0,0 +1,152 @@
+use std::io;
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::*;
+use windows::Win32::System::Threading::*;
+use windows::Win32::System::IO::*;
+
+use crate::reader::AsyncFile;
+
+struct Slot {
+    // Boxed so the OVERLAPPED keeps its address while the kernel uses it.
+    overlapped: Box<OVERLAPPED>,
+    buffer: Vec<u8>,
+    in_flight: bool,
+}
+
+// ReadFile on a handle opened with FILE_FLAG_OVERLAPPED, one event per slot.
+pub struct Overlapped {
+    file: HANDLE,
+    slots: Vec<Slot>,
+}
+
+impl Overlapped {
+    pub fn open(path: &str, buffer_size: usize, depth: usize) -> Self {
+        let file = unsafe {
+            CreateFileW(
+                &HSTRING::from(path),
+                FILE_GENERIC_READ,
+                FILE_SHARE_READ,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_OVERLAPPED,
+                None,
+            )
+            .expect("can't open file")
+        };
+
+        let slots = (0..depth)
+            .map(|_| {
+                let mut overlapped = Box::<OVERLAPPED>::default();
+                overlapped.hEvent = unsafe {
+                    CreateEventW(None, true, false, PCWSTR::null()).expect("can't create event")
+                };
+
+                Slot {
+                    overlapped,
+                    buffer: vec![0; buffer_size],
+                    in_flight: false,
+                }
+            })
+            .collect();
+
+        Self { file, slots }
+    }
+}
+
+impl AsyncFile for Overlapped {
+    fn submit(&mut self, slot: usize, offset: u64) {
+        let slot = &mut self.slots[slot];
+
+        unsafe {
+            slot.overlapped.Anonymous.Anonymous.Offset = offset as u32;
+            slot.overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
+            ResetEvent(slot.overlapped.hEvent);
+
+            // Failing with ERROR_IO_PENDING is the normal case; any other
+            // failure, the end of the file included, is picked up by
+            // GetOverlappedResult once the event is set.
+            if !ReadFile(
+                self.file,
+                Some(&mut slot.buffer),
+                None,
+                Some(&mut *slot.overlapped),
+            )
+            .as_bool()
+                && GetLastError() != ERROR_IO_PENDING
+            {
+                SetEvent(slot.overlapped.hEvent);
+            }
+        }
+
+        slot.in_flight = true;
+    }
+
+    fn complete(&mut self) -> (usize, io::Result<usize>) {
+        let pending: Vec<_> = (0..self.slots.len())
+            .filter(|&slot| self.slots[slot].in_flight)
+            .collect();
+
+        let events: Vec<_> = pending
+            .iter()
+            .map(|&slot| self.slots[slot].overlapped.hEvent)
+            .collect();
+
+        let slot = unsafe {
+            let signalled = WaitForMultipleObjects(&events, false, INFINITE);
+
+            if signalled == WAIT_FAILED {
+                panic!(
+                    "can't wait for reads: {}",
+                    io::Error::from_raw_os_error(GetLastError().0 as i32)
+                );
+            }
+
+            let index = signalled.0.wrapping_sub(WAIT_OBJECT_0.0) as usize;
+            *pending
+                .get(index)
+                .unwrap_or_else(|| panic!("unexpected wait result {:#x}", signalled.0))
+        };
+
+        let state = &mut self.slots[slot];
+        state.in_flight = false;
+
+        let mut bytes_copied = 0;
+
+        unsafe {
+            if GetOverlappedResult(self.file, &*state.overlapped, &mut bytes_copied, false)
+                .as_bool()
+            {
+                (slot, Ok(bytes_copied as usize))
+            } else if GetLastError() == ERROR_HANDLE_EOF {
+                (slot, Ok(0))
+            } else {
+                (slot, Err(io::Error::last_os_error()))
+            }
+        }
+    }
+
+    fn buffer(&mut self, slot: usize) -> &mut [u8] {
+        &mut self.slots[slot].buffer
+    }
+}
+
+impl Drop for Overlapped {
+    fn drop(&mut self) {
+        unsafe {
+            // Nothing may still be writing into the buffers once they're gone.
+            CancelIoEx(self.file, None);
+
+            for slot in &self.slots {
+                if slot.in_flight {
+                    let mut bytes_copied = 0;
+                    GetOverlappedResult(self.file, &*slot.overlapped, &mut bytes_copied, true);
+                }
+                CloseHandle(slot.overlapped.hEvent);
+            }
+
+            CloseHandle(self.file);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::reader::AsyncFile;
+
+#[cfg(unix)]
+pub fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buffer, offset)
+}
+
+#[cfg(windows)]
+pub fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buffer, offset)
+}
+
+struct Job {
+    slot: usize,
+    offset: u64,
+    buffer: Vec<u8>,
+}
+
+type Done = (usize, Vec<u8>, io::Result<usize>);
+
+// Plain positioned reads, made asynchronous by handing them to a few threads.
+// A buffer travels to the thread doing the read and comes back with the
+// result, so nobody else can touch it in the meantime.
+pub struct Pool {
+    buffers: Vec<Option<Vec<u8>>>,
+    jobs: Option<Sender<Job>>,
+    done: Receiver<Done>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    pub fn open(path: &str, buffer_size: usize, depth: usize, threads: usize) -> Self {
+        let file = Arc::new(File::open(path).expect("can't open file"));
+
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let (finished, done) = channel();
+
+        let threads = (0..threads.max(1))
+            .map(|_| {
+                let file = file.clone();
+                let queue = queue.clone();
+                let finished = finished.clone();
+
+                std::thread::spawn(move || loop {
+                    let job = queue.lock().unwrap().recv();
+
+                    let Ok(mut job) = job else {
+                        break;
+                    };
+
+                    let result = read_at(&file, &mut job.buffer, job.offset);
+                    _ = finished.send((job.slot, job.buffer, result));
+                })
+            })
+            .collect();
+
+        Self {
+            buffers: (0..depth).map(|_| Some(vec![0; buffer_size])).collect(),
+            jobs: Some(jobs),
+            done,
+            threads,
+        }
+    }
+}
+
+impl AsyncFile for Pool {
+    fn submit(&mut self, slot: usize, offset: u64) {
+        let buffer = self.buffers[slot].take().expect("slot already in flight");
+
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(Job {
+                slot,
+                offset,
+                buffer,
+            })
+            .expect("read threads are gone");
+    }
+
+    fn complete(&mut self) -> (usize, io::Result<usize>) {
+        let (slot, buffer, result) = self.done.recv().expect("read threads are gone");
+
+        self.buffers[slot] = Some(buffer);
+        (slot, result)
+    }
+
+    fn buffer(&mut self, slot: usize) -> &mut [u8] {
+        self.buffers[slot].as_mut().expect("slot still in flight")
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Closing the queue lets every thread run out of jobs and stop.
+        self.jobs = None;
+
+        for thread in self.threads.drain(..) {
+            _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::io;
+
+// A file read at explicit offsets without blocking the caller. Every slot has
+// its own buffer; a read is submitted into a free slot and later completed,
+// after which the slot's buffer holds the data until it is submitted again.
+pub trait AsyncFile {
+    // The slot must not have a read in flight.
+    fn submit(&mut self, slot: usize, offset: u64);
+
+    // Waits for any read in flight and returns its slot and how many bytes it
+    // read, 0 at the end of the file.
+    fn complete(&mut self) -> (usize, io::Result<usize>);
+
+    fn buffer(&mut self, slot: usize) -> &mut [u8];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    Overlapped,
+    IoUring,
+    Pool,
+}
+
+impl Backend {
+    pub const ALL: [Self; 3] = [Self::Overlapped, Self::IoUring, Self::Pool];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Overlapped => "overlapped",
+            Self::IoUring => "io-uring",
+            Self::Pool => "pool",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name() == value)
+    }
+
+    // The native one where there is one, positioned reads on threads
+    // everywhere else.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Self::Overlapped
+        } else if cfg!(target_os = "linux") {
+            Self::IoUring
+        } else {
+            Self::Pool
+        }
+    }
+}
+
+// Why the backend can't keep `depth` reads in flight here, if it can't.
+pub fn unsupported(backend: Backend, depth: usize) -> Option<String> {
+    match backend {
+        #[cfg(windows)]
+        Backend::Overlapped => None,
+        #[cfg(target_os = "linux")]
+        Backend::IoUring => crate::uring::unsupported(depth),
+        Backend::Pool => None,
+        #[allow(unreachable_patterns)]
+        _ => Some("not available on this platform".to_string()),
+    }
+}
+
+pub fn open(
+    path: &str,
+    backend: Backend,
+    buffer_size: usize,
+    depth: usize,
+    threads: usize,
+) -> Box<dyn AsyncFile> {
+    match backend {
+        #[cfg(windows)]
+        Backend::Overlapped => Box::new(crate::overlapped::Overlapped::open(
+            path,
+            buffer_size,
+            depth,
+        )),
+        #[cfg(target_os = "linux")]
+        Backend::IoUring => Box::new(crate::uring::Uring::open(path, buffer_size, depth)),
+        Backend::Pool => Box::new(crate::pool::Pool::open(path, buffer_size, depth, threads)),
+        #[allow(unreachable_patterns)]
+        other => panic!("the {} backend isn't available here", other.name()),
+    }
+}
@@ -0,0 +1,465 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+use crate::count::Counter;
+use crate::pool::read_at;
+use crate::reader::{self, Backend};
+
+// One way of getting a whole file through a counter, `buffer_size` bytes at a
+// time. Returns how many bytes were read.
+pub trait ReadStrategy {
+    fn name(&self) -> String;
+
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64;
+
+    // Why the strategy can't read this file here, if it can't.
+    fn unsupported(&self, _path: &str) -> Option<String> {
+        None
+    }
+
+    // Whether reads go through the page cache, so a cold run has to empty it
+    // first.
+    fn cached(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    Buffered,
+    Pread,
+    Mmap,
+    Direct,
+    Async,
+    Threads,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 6] = [
+        Self::Buffered,
+        Self::Pread,
+        Self::Mmap,
+        Self::Direct,
+        Self::Async,
+        Self::Threads,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Buffered => "buffered",
+            Self::Pread => "pread",
+            Self::Mmap => "mmap",
+            Self::Direct => "direct",
+            Self::Async => "async",
+            Self::Threads => "threads",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == value)
+    }
+
+    // The async strategy is run once for every queue depth.
+    pub fn create(
+        self,
+        backend: Backend,
+        depths: &[usize],
+        threads: usize,
+    ) -> Vec<Box<dyn ReadStrategy>> {
+        match self {
+            Self::Buffered => vec![Box::new(Buffered)],
+            Self::Pread => vec![Box::new(Pread)],
+            Self::Mmap => vec![Box::new(Mmap)],
+            Self::Direct => vec![Box::new(Direct)],
+            Self::Async => depths
+                .iter()
+                .map(|&depth| -> Box<dyn ReadStrategy> {
+                    Box::new(Async {
+                        backend,
+                        depth,
+                        threads,
+                    })
+                })
+                .collect(),
+            Self::Threads => vec![Box::new(Threads { threads })],
+        }
+    }
+}
+
+// std's BufReader in front of plain sequential reads.
+struct Buffered;
+
+impl ReadStrategy for Buffered {
+    fn name(&self) -> String {
+        "buffered".to_string()
+    }
+
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        let mut file = BufReader::new(File::open(path).expect("can't open file"));
+        let mut buffer = vec![0; buffer_size];
+        let mut offset = 0;
+
+        loop {
+            let bytes_copied = file.read(&mut buffer).expect("can't read file");
+
+            if bytes_copied == 0 {
+                return offset;
+            }
+
+            counter.count(offset, &buffer[..bytes_copied]);
+            offset += bytes_copied as u64;
+        }
+    }
+}
+
+// A positioned read for every buffer, with nothing in between.
+struct Pread;
+
+impl ReadStrategy for Pread {
+    fn name(&self) -> String {
+        "pread".to_string()
+    }
+
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        let file = File::open(path).expect("can't open file");
+        let length = file.metadata().expect("can't stat file").len();
+
+        read_range(&file, 0..length, &mut vec![0; buffer_size], counter)
+    }
+}
+
+// Positioned reads over part of a file.
+fn read_range(
+    file: &File,
+    range: std::ops::Range<u64>,
+    buffer: &mut [u8],
+    counter: &mut Counter,
+) -> u64 {
+    let mut offset = range.start;
+
+    while offset < range.end {
+        let wanted = buffer.len().min((range.end - offset) as usize);
+        let bytes_copied = read_at(file, &mut buffer[..wanted], offset).expect("can't read file");
+
+        if bytes_copied == 0 {
+            break;
+        }
+
+        counter.count(offset, &buffer[..bytes_copied]);
+        offset += bytes_copied as u64;
+    }
+
+    offset - range.start
+}
+
+// The whole file mapped at once and counted `buffer_size` bytes at a time,
+// with the kernel told it's going to be read front to back.
+struct Mmap;
+
+impl ReadStrategy for Mmap {
+    fn name(&self) -> String {
+        "mmap".to_string()
+    }
+
+    #[cfg(unix)]
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path).expect("can't open file");
+        let length = file.metadata().expect("can't stat file").len() as usize;
+
+        // There's no mapping something empty.
+        if length == 0 {
+            return 0;
+        }
+
+        unsafe {
+            let address = libc::mmap(
+                std::ptr::null_mut(),
+                length,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            );
+            assert!(
+                address != libc::MAP_FAILED,
+                "can't map file: {}",
+                std::io::Error::last_os_error()
+            );
+
+            // Only hints; reading works the same if they're ignored.
+            libc::madvise(address, length, libc::MADV_SEQUENTIAL);
+            libc::madvise(address, length, libc::MADV_WILLNEED);
+
+            let data = std::slice::from_raw_parts(address as *const u8, length);
+
+            for (index, chunk) in data.chunks(buffer_size).enumerate() {
+                counter.count((index * buffer_size) as u64, chunk);
+            }
+
+            libc::munmap(address, length);
+        }
+
+        length as u64
+    }
+
+    #[cfg(not(unix))]
+    fn read(&self, _: &str, _: usize, _: &mut Counter) -> u64 {
+        panic!("the mmap strategy isn't available here")
+    }
+
+    fn unsupported(&self, _path: &str) -> Option<String> {
+        (!cfg!(unix)).then(|| "not available on this platform".to_string())
+    }
+}
+
+// Reads that bypass the page cache. Buffers, offsets and sizes all have to be
+// multiples of the device's block size, so the buffer size is rounded up to
+// whole blocks.
+struct Direct;
+
+const BLOCK: usize = 4096;
+
+impl Direct {
+    // Some file systems, tmpfs among them, refuse O_DIRECT with EINVAL.
+    #[cfg(target_os = "linux")]
+    fn open(path: &str) -> io::Result<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn open(path: &str) -> io::Result<File> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    #[cfg(windows)]
+    fn open(path: &str) -> io::Result<File> {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+
+        std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(FILE_FLAG_NO_BUFFERING)
+            .open(path)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    fn open(_: &str) -> io::Result<File> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "not available on this platform",
+        ))
+    }
+
+    // A buffer of `size` bytes aligned to a block, taken out of a bigger Vec
+    // since a Vec only promises the alignment of its elements.
+    fn aligned(storage: &mut Vec<u8>, size: usize) -> &mut [u8] {
+        *storage = vec![0; size + BLOCK];
+        let start = storage.as_ptr().align_offset(BLOCK);
+        &mut storage[start..start + size]
+    }
+}
+
+impl ReadStrategy for Direct {
+    fn name(&self) -> String {
+        "direct".to_string()
+    }
+
+    // Some file systems only refuse at the first read.
+    fn unsupported(&self, path: &str) -> Option<String> {
+        let mut storage = vec![];
+
+        Self::open(path)
+            .and_then(|file| read_at(&file, Self::aligned(&mut storage, BLOCK), 0))
+            .err()
+            .map(|error| error.to_string())
+    }
+
+    fn cached(&self) -> bool {
+        false
+    }
+
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        let file = Self::open(path).expect("can't open file for direct reads");
+        let mut storage = vec![];
+        let buffer = Self::aligned(&mut storage, buffer_size.next_multiple_of(BLOCK));
+
+        let length = file.metadata().expect("can't stat file").len();
+        let mut offset = 0;
+
+        while offset < length {
+            // A read that came back short may leave the next one off a block
+            // boundary. It starts at the boundary before instead, and what was
+            // counted already is skipped.
+            let aligned = offset - offset % BLOCK as u64;
+            let skip = (offset - aligned) as usize;
+
+            let bytes_copied = read_at(&file, buffer, aligned).expect("can't read file");
+
+            if bytes_copied <= skip {
+                break;
+            }
+
+            counter.count(offset, &buffer[skip..bytes_copied]);
+            offset = aligned + bytes_copied as u64;
+        }
+
+        check_length(offset, length);
+        offset
+    }
+}
+
+// `depth` reads in flight at once on one of the asynchronous backends.
+struct Async {
+    backend: Backend,
+    depth: usize,
+    threads: usize,
+}
+
+impl ReadStrategy for Async {
+    fn name(&self) -> String {
+        format!("{} {}", self.backend.name(), self.depth)
+    }
+
+    fn unsupported(&self, _path: &str) -> Option<String> {
+        reader::unsupported(self.backend, self.depth)
+            .map(|reason| format!("{}; --backend pool reads everywhere", reason))
+    }
+
+    // Keeps `depth` reads in flight at increasing offsets and counts each
+    // buffer as soon as it arrives, while the reads behind it are still
+    // pending. Every slot reads one buffer's worth of the file; a read that
+    // comes back short is sent again for the rest.
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        let length = std::fs::metadata(path).expect("can't stat file").len();
+        let mut file = reader::open(path, self.backend, buffer_size, self.depth, self.threads);
+
+        // Where the next read of each slot starts and where its range ends.
+        let mut ranges = vec![0..0; self.depth];
+        let mut next_offset = 0;
+        let mut in_flight = 0;
+        let mut bytes = 0;
+
+        for (slot, range) in ranges.iter_mut().enumerate() {
+            if next_offset >= length {
+                break;
+            }
+
+            *range = next_offset..(next_offset + buffer_size as u64).min(length);
+            file.submit(slot, next_offset);
+            next_offset = range.end;
+            in_flight += 1;
+        }
+
+        while in_flight > 0 {
+            let (slot, bytes_copied) = file.complete();
+            let bytes_copied = bytes_copied.expect("can't read file");
+            in_flight -= 1;
+
+            let range = &mut ranges[slot];
+
+            // An empty read means the file ended before its length said; the
+            // check below reports it.
+            if bytes_copied == 0 {
+                continue;
+            }
+
+            // Only what this read returned, and only up to the range: the
+            // rest of the buffer is left over from an earlier read, or belongs
+            // to another slot.
+            let counted = bytes_copied.min((range.end - range.start) as usize);
+            counter.count(range.start, &file.buffer(slot)[..counted]);
+            bytes += counted as u64;
+            range.start += counted as u64;
+
+            if range.start < range.end {
+                file.submit(slot, range.start);
+                in_flight += 1;
+            } else if next_offset < length {
+                *range = next_offset..(next_offset + buffer_size as u64).min(length);
+                file.submit(slot, next_offset);
+                next_offset = range.end;
+                in_flight += 1;
+            }
+        }
+
+        check_length(bytes, length);
+        bytes
+    }
+}
+
+// Reads that stop early would count less than the file holds and look fast.
+fn check_length(bytes: u64, length: u64) {
+    assert_eq!(
+        bytes, length,
+        "read {} bytes of a {} byte file",
+        bytes, length
+    );
+}
+
+// The file cut into one range per thread, every thread reading and counting
+// its own with positioned reads. The counts are added up at the end.
+struct Threads {
+    threads: usize,
+}
+
+impl ReadStrategy for Threads {
+    fn name(&self) -> String {
+        format!("threads {}", self.threads)
+    }
+
+    fn read(&self, path: &str, buffer_size: usize, counter: &mut Counter) -> u64 {
+        let file = File::open(path).expect("can't open file");
+        let length = file.metadata().expect("can't stat file").len();
+
+        // Ranges start on buffer boundaries, so the reads are the same ones
+        // the other strategies make.
+        let buffers = length.div_ceil(buffer_size as u64);
+        let per_thread = buffers.div_ceil(self.threads.max(1) as u64).max(1) * buffer_size as u64;
+
+        let results: Vec<(u64, Counter)> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..length)
+                .step_by(per_thread as usize)
+                .map(|start| {
+                    let file = &file;
+                    let mut counter = counter.empty();
+
+                    scope.spawn(move || {
+                        let end = (start + per_thread).min(length);
+                        let mut buffer = vec![0; buffer_size];
+                        let bytes = read_range(file, start..end, &mut buffer, &mut counter);
+                        (bytes, counter)
+                    })
+                })
+                .collect();
+
+            threads
+                .into_iter()
+                .map(|thread| thread.join().expect("reading thread panicked"))
+                .collect()
+        });
+
+        results
+            .into_iter()
+            .map(|(bytes, part)| {
+                counter.merge(part);
+                bytes
+            })
+            .sum()
+    }
+}
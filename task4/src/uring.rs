@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::reader::AsyncFile;
+
+// Reads submitted to an io_uring ring, the slot travelling as user data.
+pub struct Uring {
+    ring: IoUring,
+    file: File,
+    buffers: Vec<Vec<u8>>,
+    in_flight: usize,
+}
+
+// Seccomp filters, older kernels and io_uring_disabled turn io_uring off, which
+// only shows when a ring is set up.
+pub fn unsupported(depth: usize) -> Option<String> {
+    ring(depth).err().map(|error| error.to_string())
+}
+
+fn ring(depth: usize) -> io::Result<IoUring> {
+    IoUring::new(depth.next_power_of_two() as u32)
+}
+
+impl Uring {
+    pub fn open(path: &str, buffer_size: usize, depth: usize) -> Self {
+        Self {
+            ring: ring(depth).expect("can't set up io_uring"),
+            file: File::open(path).expect("can't open file"),
+            buffers: (0..depth).map(|_| vec![0; buffer_size]).collect(),
+            in_flight: 0,
+        }
+    }
+}
+
+impl AsyncFile for Uring {
+    fn submit(&mut self, slot: usize, offset: u64) {
+        let buffer = &mut self.buffers[slot];
+
+        let read = opcode::Read::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+        )
+        .offset(offset)
+        .build()
+        .user_data(slot as u64);
+
+        // The buffer belongs to this ring until the read completes, and the
+        // ring is never bigger than the number of buffers.
+        unsafe {
+            self.ring
+                .submission()
+                .push(&read)
+                .expect("submission queue is full");
+        }
+
+        self.ring.submit().expect("can't submit read");
+        self.in_flight += 1;
+    }
+
+    fn complete(&mut self) -> (usize, io::Result<usize>) {
+        loop {
+            if let Some(entry) = self.ring.completion().next() {
+                let slot = entry.user_data() as usize;
+                let result = entry.result();
+                self.in_flight -= 1;
+
+                return if result < 0 {
+                    (slot, Err(io::Error::from_raw_os_error(-result)))
+                } else {
+                    (slot, Ok(result as usize))
+                };
+            }
+
+            self.ring.submit_and_wait(1).expect("can't wait for reads");
+        }
+    }
+
+    fn buffer(&mut self, slot: usize) -> &mut [u8] {
+        &mut self.buffers[slot]
+    }
+}
+
+impl Drop for Uring {
+    // The kernel may still be writing into the buffers.
+    fn drop(&mut self) {
+        while self.in_flight > 0 {
+            _ = self.complete();
+        }
+    }
+}
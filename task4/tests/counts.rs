@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The reference counts next to every fixture were made independently of this
+// program: a plain byte histogram, and the file decoded as UTF-8 with each
+// invalid sequence counted once, the way str::from_utf8 reports them.
+const FIXTURES: [&str; 4] = ["ascii.txt", "multilingual.txt", "invalid.bin", "empty.txt"];
+
+// Odd sizes and sizes smaller than a character split characters in every
+// possible place; several reads in flight complete out of order.
+const SIZES: &str = "1,2,3,5,7,64,1000,65536";
+const DEPTHS: &str = "1,3,8";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn backends() -> Vec<&'static str> {
+    let mut backends = vec!["pool"];
+
+    if cfg!(windows) {
+        backends.push("overlapped");
+    } else if cfg!(target_os = "linux") {
+        backends.push("io-uring");
+    }
+
+    backends
+}
+
+// Every strategy there is here. The backends only matter to the async one.
+const STRATEGIES: &str = if cfg!(unix) {
+    "buffered,pread,mmap,direct,async,threads"
+} else {
+    "buffered,pread,direct,async,threads"
+};
+
+fn counts(name: &str, mode: &str, backend: &str) -> String {
+    let output = std::env::temp_dir().join(format!(
+        "task4-test-{}-{}-{}-{}",
+        std::process::id(),
+        name,
+        mode,
+        backend
+    ));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_task4"))
+        .arg("--file")
+        .arg(fixture(name))
+        .args(["--count", mode, "--backend", backend, "--engine", "all"])
+        .args(["--sizes", SIZES, "--depth", DEPTHS, "--runs", "1"])
+        .args(["--strategy", STRATEGIES])
+        .arg("--counts")
+        .arg(&output)
+        .output()
+        .expect("can't run task4")
+        .status;
+
+    // task4 itself fails when two runs count differently, whatever strategy
+    // read the file and whatever engine counted its bytes.
+    assert!(status.success(), "{} {} {} failed", name, mode, backend);
+
+    let counts = std::fs::read_to_string(&output).expect("no counts written");
+    _ = std::fs::remove_file(&output);
+    counts
+}
+
+fn check(mode: &str) {
+    for name in FIXTURES {
+        let stem = name.split('.').next().unwrap();
+        let expected = std::fs::read_to_string(fixture(&format!("{}.{}", stem, mode)))
+            .expect("can't read reference counts");
+
+        for backend in backends() {
+            assert_eq!(
+                counts(name, mode, backend),
+                expected,
+                "{} counted as {} with {}",
+                name,
+                mode,
+                backend
+            );
+        }
+    }
+}
+
+#[test]
+fn byte_histogram_matches_reference() {
+    check("bytes");
+}
+
+#[test]
+fn utf8_scalars_match_reference() {
+    check("chars");
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const CONTENTS: [&str; 4] = ["random", "english", "skewed", "multilingual"];
+
+// Every strategy reads the file once, checked against its histogram.
+const VERIFY: [&str; 8] = [
+    "--verify", "--strategy", "all", "--sizes", "4096", "--runs", "1", "--file",
+];
+
+fn status(args: &[&str], path: &Path) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_task4"))
+        .args(args)
+        .arg(path)
+        .output()
+        .expect("can't run task4")
+        .status
+        .code()
+}
+
+fn run(args: &[&str], path: &Path) {
+    assert_eq!(status(args, path), Some(0), "task4 {:?} {:?} failed", args, path);
+}
+
+fn generate(content: &str, seed: &str, path: &Path) -> Vec<u8> {
+    let command = ["generate", "--size", "300K", "--content", content];
+    run(&[&command[..], &["--seed", seed, "--output"]].concat(), path);
+
+    std::fs::read(path).expect("nothing generated")
+}
+
+fn temporary(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("task4-test-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn generated_files_are_reproducible_and_match_their_histograms() {
+    for content in CONTENTS {
+        let path = temporary(content);
+        let histogram = PathBuf::from(format!("{}.bytes", path.display()));
+
+        let first = generate(content, "7", &path);
+        assert_eq!(first.len(), 300 << 10, "{} has the wrong size", content);
+
+        if content != "random" {
+            assert!(
+                std::str::from_utf8(&first).is_ok(),
+                "{} isn't UTF-8",
+                content
+            );
+        }
+
+        run(&VERIFY, &path);
+
+        assert_eq!(generate(content, "7", &path), first, "{} changed", content);
+        assert_ne!(
+            generate(content, "8", &path),
+            first,
+            "{} ignores the seed",
+            content
+        );
+
+        _ = std::fs::remove_file(&path);
+        _ = std::fs::remove_file(&histogram);
+    }
+}
+
+#[test]
+fn verify_fails_the_run_when_counts_differ() {
+    let path = temporary("verify");
+    let histogram = PathBuf::from(format!("{}.bytes", path.display()));
+
+    generate("skewed", "7", &path);
+
+    // The file no longer matches what generate counted.
+    let mut contents = std::fs::read(&path).expect("nothing generated");
+    contents[0] ^= 1;
+    std::fs::write(&path, contents).expect("can't change the file");
+
+    let verified = status(&VERIFY, &path);
+
+    _ = std::fs::remove_file(&path);
+    _ = std::fs::remove_file(&histogram);
+
+    assert_eq!(verified, Some(1));
+}